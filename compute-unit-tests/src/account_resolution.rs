@@ -0,0 +1,109 @@
+//! Extra account metas resolved by the account resolution benchmarks
+//!
+//! The benchmark instructions take the validation account first, followed by
+//! `BASE_ACCOUNT_COUNT` base accounts and then every extra account. The first
+//! base account holds `source_account_data`, and the second is used as a key.
+
+use {
+    solana_program_error::ProgramError,
+    solana_pubkey::{Pubkey, PUBKEY_BYTES},
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, pubkey_data::PubkeyData, seeds::Seed,
+        state::ExtraAccountMetaList,
+    },
+};
+
+/// Instruction whose extra account metas are resolved by the benchmarks
+pub struct BenchmarkHookInstruction;
+impl SplDiscriminate for BenchmarkHookInstruction {
+    const SPL_DISCRIMINATOR: ArrayDiscriminator =
+        ArrayDiscriminator::new([7; ArrayDiscriminator::LENGTH]);
+}
+
+/// Number of accounts that come before the extra accounts
+pub const BASE_ACCOUNT_COUNT: usize = 2;
+
+/// Address of the fixed extra account, which also owns the external PDA
+pub const FIXED_ADDRESS: Pubkey = Pubkey::new_from_array([9; PUBKEY_BYTES]);
+
+/// Data of the first base account: 32 bytes of seed material followed by a
+/// pubkey
+pub fn source_account_data(key: &Pubkey) -> Vec<u8> {
+    let mut data = vec![5; PUBKEY_BYTES];
+    data.extend_from_slice(key.as_ref());
+    data
+}
+
+/// Extra account metas covering every kind of address configuration, with
+/// PDAs seeded from literals, instruction data, account keys, and account
+/// data
+pub fn benchmark_extra_account_metas() -> Result<Vec<ExtraAccountMeta>, ProgramError> {
+    Ok(vec![
+        ExtraAccountMeta::new_with_pubkey(&FIXED_ADDRESS, false, false)?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"counter".to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+            ],
+            false,
+            true,
+        )?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"delegate".to_vec(),
+                },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: 0,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::InstructionData {
+                    index: 0,
+                    length: 8,
+                },
+                Seed::AccountKey { index: 0 },
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_pubkey_data(
+            &PubkeyData::AccountData {
+                account_index: 0,
+                data_index: 32,
+            },
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            2,
+            &[
+                Seed::AccountKey { index: 1 },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: 8,
+                    length: 16,
+                },
+            ],
+            false,
+            false,
+        )?,
+    ])
+}
+
+/// Builds the validation account data holding `benchmark_extra_account_metas`
+pub fn benchmark_validation_data() -> Result<Vec<u8>, ProgramError> {
+    let extra_account_metas = benchmark_extra_account_metas()?;
+    let mut data = vec![0; ExtraAccountMetaList::size_of(extra_account_metas.len())?];
+    ExtraAccountMetaList::init::<BenchmarkHookInstruction>(&mut data, &extra_account_metas)?;
+    Ok(data)
+}
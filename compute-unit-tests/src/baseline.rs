@@ -0,0 +1,274 @@
+//! Account resolution as `spl-tlv-account-resolution` did it before
+//! resolution stopped allocating, kept as the baseline for the account
+//! resolution benchmarks
+
+use {
+    solana_account_info::AccountInfo,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_program_error::ProgramError,
+    solana_pubkey::{Pubkey, PUBKEY_BYTES},
+    spl_discriminator::SplDiscriminate,
+    spl_pod::slice::PodSlice,
+    spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, error::AccountResolutionError, pubkey_data::PubkeyData,
+        seeds::Seed, state::ExtraAccountMetaList,
+    },
+    spl_type_length_value::state::{TlvState, TlvStateBorrowed},
+};
+
+const U8_TOP_BIT: u8 = 1 << 7;
+
+fn account_info_to_meta(account_info: &AccountInfo) -> AccountMeta {
+    AccountMeta {
+        pubkey: *account_info.key,
+        is_signer: account_info.is_signer,
+        is_writable: account_info.is_writable,
+    }
+}
+
+fn de_escalate_account_meta(account_meta: &mut AccountMeta, account_metas: &[AccountMeta]) {
+    let maybe_highest_privileges = account_metas
+        .iter()
+        .filter(|&x| x.pubkey == account_meta.pubkey)
+        .map(|x| (x.is_signer, x.is_writable))
+        .reduce(|acc, x| (acc.0 || x.0, acc.1 || x.1));
+    if let Some((is_signer, is_writable)) = maybe_highest_privileges {
+        if !is_signer && is_signer != account_meta.is_signer {
+            account_meta.is_signer = false;
+        }
+        if !is_writable && is_writable != account_meta.is_writable {
+            account_meta.is_writable = false;
+        }
+    }
+}
+
+fn resolve_pda<'a, F>(
+    seeds: &[Seed],
+    instruction_data: &[u8],
+    program_id: &Pubkey,
+    get_account_key_data_fn: F,
+) -> Result<Pubkey, ProgramError>
+where
+    F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
+{
+    let mut pda_seeds: Vec<&[u8]> = vec![];
+    for config in seeds {
+        match config {
+            Seed::Uninitialized => (),
+            Seed::Literal { bytes } => pda_seeds.push(bytes),
+            Seed::InstructionData { index, length } => {
+                let arg_start = *index as usize;
+                let arg_end = arg_start + *length as usize;
+                if arg_end > instruction_data.len() {
+                    return Err(AccountResolutionError::InstructionDataTooSmall.into());
+                }
+                pda_seeds.push(&instruction_data[arg_start..arg_end]);
+            }
+            Seed::AccountKey { index } => {
+                let account_index = *index as usize;
+                let address = get_account_key_data_fn(account_index)
+                    .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                    .0;
+                pda_seeds.push(address.as_ref());
+            }
+            Seed::AccountData {
+                account_index,
+                data_index,
+                length,
+            } => {
+                let account_index = *account_index as usize;
+                let account_data = get_account_key_data_fn(account_index)
+                    .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                    .1
+                    .ok_or::<ProgramError>(AccountResolutionError::AccountDataNotFound.into())?;
+                let arg_start = *data_index as usize;
+                let arg_end = arg_start + *length as usize;
+                if account_data.len() < arg_end {
+                    return Err(AccountResolutionError::AccountDataTooSmall.into());
+                }
+                pda_seeds.push(&account_data[arg_start..arg_end]);
+            }
+        }
+    }
+    Ok(Pubkey::find_program_address(&pda_seeds, program_id).0)
+}
+
+fn resolve_key_data<'a, F>(
+    key_data: &PubkeyData,
+    instruction_data: &[u8],
+    get_account_key_data_fn: F,
+) -> Result<Pubkey, ProgramError>
+where
+    F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
+{
+    match key_data {
+        PubkeyData::Uninitialized => Err(ProgramError::InvalidAccountData),
+        PubkeyData::InstructionData { index } => {
+            let key_start = *index as usize;
+            let key_end = key_start + PUBKEY_BYTES;
+            if key_end > instruction_data.len() {
+                return Err(AccountResolutionError::InstructionDataTooSmall.into());
+            }
+            Ok(Pubkey::new_from_array(
+                instruction_data[key_start..key_end].try_into().unwrap(),
+            ))
+        }
+        PubkeyData::AccountData {
+            account_index,
+            data_index,
+        } => {
+            let account_index = *account_index as usize;
+            let account_data = get_account_key_data_fn(account_index)
+                .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                .1
+                .ok_or::<ProgramError>(AccountResolutionError::AccountDataNotFound.into())?;
+            let arg_start = *data_index as usize;
+            let arg_end = arg_start + PUBKEY_BYTES;
+            if account_data.len() < arg_end {
+                return Err(AccountResolutionError::AccountDataTooSmall.into());
+            }
+            Ok(Pubkey::new_from_array(
+                account_data[arg_start..arg_end].try_into().unwrap(),
+            ))
+        }
+    }
+}
+
+fn resolve<'a, F>(
+    extra_meta: &ExtraAccountMeta,
+    instruction_data: &[u8],
+    program_id: &Pubkey,
+    get_account_key_data_fn: F,
+) -> Result<AccountMeta, ProgramError>
+where
+    F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
+{
+    match extra_meta.discriminator {
+        0 => AccountMeta::try_from(extra_meta),
+        x if x == 1 || x >= U8_TOP_BIT => {
+            let program_id = if x == 1 {
+                program_id
+            } else {
+                get_account_key_data_fn(x.saturating_sub(U8_TOP_BIT) as usize)
+                    .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                    .0
+            };
+            let seeds = Seed::unpack_address_config(&extra_meta.address_config)?;
+            Ok(AccountMeta {
+                pubkey: resolve_pda(
+                    &seeds,
+                    instruction_data,
+                    program_id,
+                    get_account_key_data_fn,
+                )?,
+                is_signer: extra_meta.is_signer.into(),
+                is_writable: extra_meta.is_writable.into(),
+            })
+        }
+        2 => {
+            let key_data = PubkeyData::unpack(&extra_meta.address_config)?;
+            Ok(AccountMeta {
+                pubkey: resolve_key_data(&key_data, instruction_data, get_account_key_data_fn)?,
+                is_signer: extra_meta.is_signer.into(),
+                is_writable: extra_meta.is_writable.into(),
+            })
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Baseline `ExtraAccountMetaList::check_account_infos`, which borrows the
+/// data of every account again for every extra account
+pub fn check_account_infos<T: SplDiscriminate>(
+    account_infos: &[AccountInfo],
+    instruction_data: &[u8],
+    program_id: &Pubkey,
+    data: &[u8],
+) -> Result<(), ProgramError> {
+    let state = TlvStateBorrowed::unpack(data).unwrap();
+    let extra_meta_list = ExtraAccountMetaList::unpack_with_tlv_state::<T>(&state)?;
+    let extra_account_metas = extra_meta_list.data();
+
+    let initial_accounts_len = account_infos.len() - extra_account_metas.len();
+
+    let provided_metas = account_infos
+        .iter()
+        .map(account_info_to_meta)
+        .collect::<Vec<_>>();
+
+    for (i, config) in extra_account_metas.iter().enumerate() {
+        let meta = {
+            let account_key_data_refs = account_infos
+                .iter()
+                .map(|info| {
+                    let key = *info.key;
+                    let data = info.try_borrow_data()?;
+                    Ok((key, data))
+                })
+                .collect::<Result<Vec<_>, ProgramError>>()?;
+
+            resolve(config, instruction_data, program_id, |usize| {
+                account_key_data_refs
+                    .get(usize)
+                    .map(|(pubkey, opt_data)| (pubkey, Some(opt_data.as_ref())))
+            })?
+        };
+
+        let expected_index = i
+            .checked_add(initial_accounts_len)
+            .ok_or::<ProgramError>(AccountResolutionError::CalculationFailure.into())?;
+        if provided_metas.get(expected_index) != Some(&meta) {
+            return Err(AccountResolutionError::IncorrectAccount.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Baseline `ExtraAccountMetaList::add_to_cpi_instruction`, which borrows
+/// the data of every CPI account again for every extra account
+pub fn add_to_cpi_instruction<'a, T: SplDiscriminate>(
+    cpi_instruction: &mut Instruction,
+    cpi_account_infos: &mut Vec<AccountInfo<'a>>,
+    data: &[u8],
+    account_infos: &[AccountInfo<'a>],
+) -> Result<(), ProgramError> {
+    let state = TlvStateBorrowed::unpack(data)?;
+    let bytes = state.get_first_bytes::<T>()?;
+    let extra_account_metas = PodSlice::<ExtraAccountMeta>::unpack(bytes)?;
+
+    for extra_meta in extra_account_metas.data().iter() {
+        let mut meta = {
+            let account_key_data_refs = cpi_account_infos
+                .iter()
+                .map(|info| {
+                    let key = *info.key;
+                    let data = info.try_borrow_data()?;
+                    Ok((key, data))
+                })
+                .collect::<Result<Vec<_>, ProgramError>>()?;
+
+            resolve(
+                extra_meta,
+                &cpi_instruction.data,
+                &cpi_instruction.program_id,
+                |usize| {
+                    account_key_data_refs
+                        .get(usize)
+                        .map(|(pubkey, opt_data)| (pubkey, Some(opt_data.as_ref())))
+                },
+            )?
+        };
+        de_escalate_account_meta(&mut meta, &cpi_instruction.accounts);
+
+        let account_info = account_infos
+            .iter()
+            .find(|&x| *x.key == meta.pubkey)
+            .ok_or(AccountResolutionError::IncorrectAccount)?
+            .clone();
+
+        cpi_instruction.accounts.push(meta);
+        cpi_account_infos.push(account_info);
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Program for measuring the compute units consumed by the libraries in this
+//! repository.
+//!
+//! Every benchmark comes in pairs: one instruction does the work through the
+//! library, and the other does the same work the way it was done before the
+//! library provided a faster path. Compute units are only metered when the
+//! program runs as SBF, so the numbers are meaningful with `cargo test-sbf`.
+
+#![allow(clippy::arithmetic_side_effects)]
+#![deny(missing_docs)]
+#![cfg_attr(not(test), forbid(unsafe_code))]
+
+pub mod account_resolution;
+mod baseline;
+mod entrypoint;
+pub mod processor;
+pub mod tlv;
+
+/// Instructions supported by the benchmark program
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BenchmarkInstruction {
+    /// Reads every entry of the TLV data in account 0 through
+    /// `TlvStateBorrowed`, which walks the headers on every lookup
+    LinearTlvLookups,
+    /// Reads every entry of the TLV data in account 0 through
+    /// `IndexedTlvState`, including the cost of building the index
+    IndexedTlvLookups,
+    /// Checks the extra accounts against the validation data in account 0
+    /// through `ExtraAccountMetaList::check_account_infos`, with the rest of
+    /// the instruction data as the hook instruction data
+    CheckAccountInfos,
+    /// Same as `CheckAccountInfos`, resolving every extra account the way
+    /// it was done before resolution stopped allocating
+    CheckAccountInfosBaseline,
+    /// Adds the extra accounts for the validation data in account 0 to a CPI
+    /// instruction through `ExtraAccountMetaList::add_to_cpi_instruction`,
+    /// with the rest of the instruction data as the CPI instruction data
+    AddToCpiInstruction,
+    /// Same as `AddToCpiInstruction`, resolving every extra account the way
+    /// it was done before resolution stopped allocating
+    AddToCpiInstructionBaseline,
+}
+impl BenchmarkInstruction {
+    /// Unpacks the instruction from its first byte
+    pub fn unpack(input: &[u8]) -> Option<Self> {
+        match input.first()? {
+            0 => Some(Self::LinearTlvLookups),
+            1 => Some(Self::IndexedTlvLookups),
+            2 => Some(Self::CheckAccountInfos),
+            3 => Some(Self::CheckAccountInfosBaseline),
+            4 => Some(Self::AddToCpiInstruction),
+            5 => Some(Self::AddToCpiInstructionBaseline),
+            _ => None,
+        }
+    }
+
+    /// Packs the instruction into its instruction data
+    pub fn pack(self) -> Vec<u8> {
+        vec![self as u8]
+    }
+}
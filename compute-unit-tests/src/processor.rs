@@ -0,0 +1,129 @@
+//! Program state processor
+
+use {
+    crate::{
+        account_resolution::{BenchmarkHookInstruction, BASE_ACCOUNT_COUNT},
+        baseline,
+        tlv::read_benchmark_entries,
+        BenchmarkInstruction,
+    },
+    solana_account_info::{next_account_info, AccountInfo},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_program_error::{ProgramError, ProgramResult},
+    solana_pubkey::Pubkey,
+    spl_tlv_account_resolution::state::ExtraAccountMetaList,
+    spl_type_length_value::{indexed::IndexedTlvState, state::TlvStateBorrowed},
+};
+
+fn process_linear_tlv_lookups(accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info = next_account_info(&mut accounts.iter())?;
+    let data = account_info.try_borrow_data()?;
+    let state = TlvStateBorrowed::unpack(&data)?;
+    read_benchmark_entries(&state)?;
+    Ok(())
+}
+
+fn process_indexed_tlv_lookups(accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info = next_account_info(&mut accounts.iter())?;
+    let data = account_info.try_borrow_data()?;
+    let state = IndexedTlvState::new(TlvStateBorrowed::unpack(&data)?)?;
+    read_benchmark_entries(&state)?;
+    Ok(())
+}
+
+fn process_check_account_infos(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+    baseline: bool,
+) -> ProgramResult {
+    let (validation_info, account_infos) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let data = validation_info.try_borrow_data()?;
+    if baseline {
+        baseline::check_account_infos::<BenchmarkHookInstruction>(
+            account_infos,
+            instruction_data,
+            program_id,
+            &data,
+        )
+    } else {
+        ExtraAccountMetaList::check_account_infos::<BenchmarkHookInstruction>(
+            account_infos,
+            instruction_data,
+            program_id,
+            &data,
+        )
+    }
+}
+
+fn process_add_to_cpi_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+    baseline: bool,
+) -> ProgramResult {
+    let (validation_info, account_infos) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let mut cpi_account_infos = account_infos
+        .get(..BASE_ACCOUNT_COUNT)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?
+        .to_vec();
+    let mut cpi_instruction = Instruction::new_with_bytes(
+        *program_id,
+        instruction_data,
+        cpi_account_infos
+            .iter()
+            .map(|info| AccountMeta {
+                pubkey: *info.key,
+                is_signer: info.is_signer,
+                is_writable: info.is_writable,
+            })
+            .collect(),
+    );
+    let data = validation_info.try_borrow_data()?;
+    if baseline {
+        baseline::add_to_cpi_instruction::<BenchmarkHookInstruction>(
+            &mut cpi_instruction,
+            &mut cpi_account_infos,
+            &data,
+            account_infos,
+        )
+    } else {
+        ExtraAccountMetaList::add_to_cpi_instruction::<BenchmarkHookInstruction>(
+            &mut cpi_instruction,
+            &mut cpi_account_infos,
+            &data,
+            account_infos,
+        )
+    }
+}
+
+/// Processes a `BenchmarkInstruction`
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let instruction =
+        BenchmarkInstruction::unpack(input).ok_or(ProgramError::InvalidInstructionData)?;
+    let instruction_data = &input[1..];
+    match instruction {
+        BenchmarkInstruction::LinearTlvLookups => process_linear_tlv_lookups(accounts),
+        BenchmarkInstruction::IndexedTlvLookups => process_indexed_tlv_lookups(accounts),
+        BenchmarkInstruction::CheckAccountInfos => {
+            process_check_account_infos(program_id, accounts, instruction_data, false)
+        }
+        BenchmarkInstruction::CheckAccountInfosBaseline => {
+            process_check_account_infos(program_id, accounts, instruction_data, true)
+        }
+        BenchmarkInstruction::AddToCpiInstruction => {
+            process_add_to_cpi_instruction(program_id, accounts, instruction_data, false)
+        }
+        BenchmarkInstruction::AddToCpiInstructionBaseline => {
+            process_add_to_cpi_instruction(program_id, accounts, instruction_data, true)
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! TLV entries read by the lookup benchmarks
+
+use {
+    bytemuck::{Pod, Zeroable},
+    solana_program_error::ProgramError,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_pod::primitives::PodU64,
+    spl_type_length_value::state::{TlvState, TlvStateMut},
+    std::mem::size_of,
+};
+
+/// Number of times every entry is read in a single instruction
+pub const LOOKUP_ROUNDS: usize = 4;
+
+macro_rules! benchmark_entries {
+    ($($name:ident => $byte:literal),*) => {
+        $(
+            #[doc = concat!("Benchmark TLV entry `", stringify!($name), "`")]
+            #[repr(transparent)]
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+            pub struct $name(pub PodU64);
+            impl SplDiscriminate for $name {
+                const SPL_DISCRIMINATOR: ArrayDiscriminator =
+                    ArrayDiscriminator::new([$byte; ArrayDiscriminator::LENGTH]);
+            }
+        )*
+
+        /// Builds TLV data containing one of every benchmark entry, each
+        /// holding its discriminator byte
+        pub fn benchmark_tlv_data() -> Result<Vec<u8>, ProgramError> {
+            let count = [$(stringify!($name)),*].len();
+            let entry_len = TlvStateMut::get_base_len() + size_of::<PodU64>();
+            let mut data = vec![0; entry_len * count];
+            let mut state = TlvStateMut::unpack(&mut data)?;
+            $(
+                state.init_value::<$name>(false)?.0 .0 = PodU64::from($byte);
+            )*
+            Ok(data)
+        }
+
+        /// Reads every benchmark entry `LOOKUP_ROUNDS` times, returning the
+        /// sum of all values read
+        pub fn read_benchmark_entries<S: TlvState>(state: &S) -> Result<u64, ProgramError> {
+            let mut sum = 0u64;
+            for _ in 0..LOOKUP_ROUNDS {
+                $(
+                    sum += u64::from(state.get_first_value::<$name>()?.0);
+                )*
+            }
+            Ok(sum)
+        }
+    };
+}
+
+benchmark_entries!(
+    EntryA => 1,
+    EntryB => 2,
+    EntryC => 3,
+    EntryD => 4,
+    EntryE => 5,
+    EntryF => 6
+);
@@ -0,0 +1,165 @@
+#![allow(clippy::arithmetic_side_effects)]
+
+use {
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        signer::Signer,
+        transaction::Transaction,
+    },
+    spl_compute_unit_tests::{
+        account_resolution::{
+            benchmark_validation_data, source_account_data, BenchmarkHookInstruction,
+        },
+        processor::process_instruction,
+        tlv::benchmark_tlv_data,
+        BenchmarkInstruction,
+    },
+    spl_tlv_account_resolution::state::ExtraAccountMetaList,
+    std::collections::HashMap,
+};
+
+async fn units_consumed(
+    context: &mut solana_program_test::ProgramTestContext,
+    instruction: Instruction,
+) -> u64 {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    simulation.result.unwrap().unwrap();
+    simulation.simulation_details.unwrap().units_consumed
+}
+
+#[tokio::test]
+async fn indexed_lookups_use_fewer_compute_units() {
+    let program_id = Pubkey::new_unique();
+    let tlv_address = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "spl_compute_unit_tests",
+        program_id,
+        processor!(process_instruction),
+    );
+    program_test.add_account(
+        tlv_address,
+        Account {
+            lamports: 1_000_000_000,
+            data: benchmark_tlv_data().unwrap(),
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let mut context = program_test.start_with_context().await;
+
+    let instruction = |benchmark: BenchmarkInstruction| Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(tlv_address, false)],
+        data: benchmark.pack(),
+    };
+    let linear = units_consumed(
+        &mut context,
+        instruction(BenchmarkInstruction::LinearTlvLookups),
+    )
+    .await;
+    let indexed = units_consumed(
+        &mut context,
+        instruction(BenchmarkInstruction::IndexedTlvLookups),
+    )
+    .await;
+    println!("TLV lookups: linear {linear} CU, indexed {indexed} CU");
+    // Natively-run programs aren't metered, so only SBF runs can be compared
+    if cfg!(feature = "test-sbf") {
+        assert!(indexed < linear);
+    }
+}
+
+#[tokio::test]
+async fn account_resolution_uses_fewer_compute_units() {
+    let program_id = Pubkey::new_unique();
+    let validation_address = Pubkey::new_unique();
+    let source_address = Pubkey::new_unique();
+    let authority_address = Pubkey::new_unique();
+    let source_data = source_account_data(&Pubkey::new_unique());
+    let mut program_test = ProgramTest::new(
+        "spl_compute_unit_tests",
+        program_id,
+        processor!(process_instruction),
+    );
+    let mut account_datas = HashMap::new();
+    for (address, data) in [
+        (validation_address, benchmark_validation_data().unwrap()),
+        (source_address, source_data),
+    ] {
+        program_test.add_account(
+            address,
+            Account {
+                lamports: 1_000_000_000,
+                data: data.clone(),
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        account_datas.insert(address, data);
+    }
+    let mut context = program_test.start_with_context().await;
+
+    let hook_instruction_data = 42u64.to_le_bytes();
+    let mut hook_instruction = Instruction::new_with_bytes(
+        program_id,
+        &hook_instruction_data,
+        vec![
+            AccountMeta::new_readonly(source_address, false),
+            AccountMeta::new_readonly(authority_address, false),
+        ],
+    );
+    ExtraAccountMetaList::add_to_instruction::<BenchmarkHookInstruction, _, _>(
+        &mut hook_instruction,
+        |address| {
+            let data = account_datas.get(&address).cloned();
+            async move { Ok(data) }
+        },
+        &account_datas[&validation_address],
+    )
+    .await
+    .unwrap();
+
+    let instruction = |benchmark: BenchmarkInstruction| {
+        let mut data = benchmark.pack();
+        data.extend_from_slice(&hook_instruction_data);
+        let mut accounts = vec![AccountMeta::new_readonly(validation_address, false)];
+        accounts.extend_from_slice(&hook_instruction.accounts);
+        Instruction {
+            program_id,
+            accounts,
+            data,
+        }
+    };
+    let mut units = vec![];
+    for benchmark in [
+        BenchmarkInstruction::CheckAccountInfos,
+        BenchmarkInstruction::CheckAccountInfosBaseline,
+        BenchmarkInstruction::AddToCpiInstruction,
+        BenchmarkInstruction::AddToCpiInstructionBaseline,
+    ] {
+        units.push(units_consumed(&mut context, instruction(benchmark)).await);
+    }
+    let [check, check_baseline, add_to_cpi, add_to_cpi_baseline] = units[..] else {
+        unreachable!();
+    };
+    println!("check_account_infos: {check} CU, baseline {check_baseline} CU");
+    println!("add_to_cpi_instruction: {add_to_cpi} CU, baseline {add_to_cpi_baseline} CU");
+    // Natively-run programs aren't metered, so only SBF runs can be compared
+    if cfg!(feature = "test-sbf") {
+        assert!(check < check_baseline);
+        assert!(add_to_cpi < add_to_cpi_baseline);
+    }
+}
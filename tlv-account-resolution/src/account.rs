@@ -5,66 +5,104 @@
 //! addresses derived from a collection of seeds
 
 use {
-    crate::{error::AccountResolutionError, pubkey_data::PubkeyData, seeds::Seed},
+    crate::{
+        error::AccountResolutionError,
+        pubkey_data::PubkeyData,
+        seeds::Seed,
+        trace::{AddressTrace, SeedTrace},
+        validation::ExtraAccountMetaIssueKind,
+    },
     bytemuck::{Pod, Zeroable},
     solana_account_info::AccountInfo,
     solana_instruction::AccountMeta,
     solana_program_error::ProgramError,
-    solana_pubkey::{Pubkey, PUBKEY_BYTES},
+    solana_pubkey::{Pubkey, MAX_SEEDS, PUBKEY_BYTES},
     spl_pod::primitives::PodBool,
 };
 
-/// Resolve a program-derived address (PDA) from the instruction data
-/// and the accounts that have already been resolved
-fn resolve_pda<'a, F>(
-    seeds: &[Seed],
-    instruction_data: &[u8],
-    program_id: &Pubkey,
+/// Resolve the bytes of the seeds of a program-derived address (PDA) from the
+/// instruction data and the accounts that have already been resolved.
+///
+/// The seeds are read straight from the packed address configuration, with
+/// literals referenced in place, so that resolving doesn't allocate. Returns
+/// the bytes of every seed and the number of seeds.
+fn resolve_pda_seeds<'a: 'b, 'b, F>(
+    address_config: &'b [u8; 32],
+    instruction_data: &'b [u8],
     get_account_key_data_fn: F,
-) -> Result<Pubkey, ProgramError>
+) -> Result<([&'b [u8]; MAX_SEEDS], usize), ProgramError>
 where
     F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
 {
-    let mut pda_seeds: Vec<&[u8]> = vec![];
-    for config in seeds {
-        match config {
-            Seed::Uninitialized => (),
-            Seed::Literal { bytes } => pda_seeds.push(bytes),
-            Seed::InstructionData { index, length } => {
-                let arg_start = *index as usize;
-                let arg_end = arg_start + *length as usize;
-                if arg_end > instruction_data.len() {
-                    return Err(AccountResolutionError::InstructionDataTooSmall.into());
-                }
-                pda_seeds.push(&instruction_data[arg_start..arg_end]);
-            }
-            Seed::AccountKey { index } => {
-                let account_index = *index as usize;
-                let address = get_account_key_data_fn(account_index)
-                    .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
-                    .0;
-                pda_seeds.push(address.as_ref());
+    let mut pda_seeds: [&[u8]; MAX_SEEDS] = [&[]; MAX_SEEDS];
+    let mut num_seeds = 0;
+    let mut i = 0;
+    while i < address_config.len() {
+        let packed_seed = &address_config[i..];
+        let seed_bytes = match packed_seed[0] {
+            0 => break,
+            // `Seed::Literal`, without copying its bytes
+            1 => {
+                let length = *packed_seed
+                    .get(1)
+                    .ok_or::<ProgramError>(AccountResolutionError::InvalidBytesForSeed.into())?
+                    as usize;
+                i += 2 + length;
+                packed_seed
+                    .get(2..2 + length)
+                    .ok_or::<ProgramError>(AccountResolutionError::InvalidBytesForSeed.into())?
             }
-            Seed::AccountData {
-                account_index,
-                data_index,
-                length,
-            } => {
-                let account_index = *account_index as usize;
-                let account_data = get_account_key_data_fn(account_index)
-                    .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
-                    .1
-                    .ok_or::<ProgramError>(AccountResolutionError::AccountDataNotFound.into())?;
-                let arg_start = *data_index as usize;
-                let arg_end = arg_start + *length as usize;
-                if account_data.len() < arg_end {
-                    return Err(AccountResolutionError::AccountDataTooSmall.into());
+            _ => {
+                let seed = Seed::unpack(packed_seed)?;
+                i += seed.tlv_size() as usize;
+                match seed {
+                    Seed::InstructionData { index, length } => {
+                        let arg_start = index as usize;
+                        let arg_end = arg_start + length as usize;
+                        if arg_end > instruction_data.len() {
+                            return Err(AccountResolutionError::InstructionDataTooSmall.into());
+                        }
+                        &instruction_data[arg_start..arg_end]
+                    }
+                    Seed::AccountKey { index } => {
+                        let account_index = index as usize;
+                        let address = get_account_key_data_fn(account_index)
+                            .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                            .0;
+                        address.as_ref()
+                    }
+                    Seed::AccountData {
+                        account_index,
+                        data_index,
+                        length,
+                    } => {
+                        let account_index = account_index as usize;
+                        let account_data = get_account_key_data_fn(account_index)
+                            .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
+                            .1
+                            .ok_or::<ProgramError>(
+                                AccountResolutionError::AccountDataNotFound.into(),
+                            )?;
+                        let arg_start = data_index as usize;
+                        let arg_end = arg_start + length as usize;
+                        if account_data.len() < arg_end {
+                            return Err(AccountResolutionError::AccountDataTooSmall.into());
+                        }
+                        &account_data[arg_start..arg_end]
+                    }
+                    Seed::Uninitialized | Seed::Literal { .. } => {
+                        return Err(AccountResolutionError::InvalidSeedConfig.into())
+                    }
                 }
-                pda_seeds.push(&account_data[arg_start..arg_end]);
             }
-        }
+        };
+        *pda_seeds
+            .get_mut(num_seeds)
+            .ok_or::<ProgramError>(AccountResolutionError::SeedConfigsTooLarge.into())? =
+            seed_bytes;
+        num_seeds += 1;
     }
-    Ok(Pubkey::find_program_address(&pda_seeds, program_id).0)
+    Ok((pda_seeds, num_seeds))
 }
 
 /// Resolve a pubkey from a pubkey data configuration.
@@ -214,41 +252,160 @@ impl ExtraAccountMeta {
     where
         F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
     {
-        match self.discriminator {
-            0 => AccountMeta::try_from(self),
+        self.resolve_traced(instruction_data, program_id, get_account_key_data_fn, None)
+    }
+
+    /// Resolve an `ExtraAccountMeta` into an `AccountMeta` like `resolve`,
+    /// also returning how its address was resolved
+    pub fn resolve_with_trace<'a, F>(
+        &self,
+        instruction_data: &[u8],
+        program_id: &Pubkey,
+        get_account_key_data_fn: F,
+    ) -> Result<(AccountMeta, AddressTrace), ProgramError>
+    where
+        F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
+    {
+        let mut address_trace = AddressTrace::Pubkey;
+        let meta = self.resolve_traced(
+            instruction_data,
+            program_id,
+            get_account_key_data_fn,
+            Some(&mut address_trace),
+        )?;
+        Ok((meta, address_trace))
+    }
+
+    /// Resolve an `ExtraAccountMeta` into an `AccountMeta`, writing how its
+    /// address was resolved to `address_trace`, if given
+    pub(crate) fn resolve_traced<'a, F>(
+        &self,
+        instruction_data: &[u8],
+        program_id: &Pubkey,
+        get_account_key_data_fn: F,
+        address_trace: Option<&mut AddressTrace>,
+    ) -> Result<AccountMeta, ProgramError>
+    where
+        F: Fn(usize) -> Option<(&'a Pubkey, Option<&'a [u8]>)>,
+    {
+        let pubkey = match self.discriminator {
+            0 => {
+                if let Some(address_trace) = address_trace {
+                    *address_trace = AddressTrace::Pubkey;
+                }
+                Pubkey::from(self.address_config)
+            }
             x if x == 1 || x >= U8_TOP_BIT => {
-                let program_id = if x == 1 {
-                    program_id
+                let (program_index, program_id) = if x == 1 {
+                    (None, program_id)
                 } else {
-                    get_account_key_data_fn(x.saturating_sub(U8_TOP_BIT) as usize)
+                    let program_index = x.saturating_sub(U8_TOP_BIT) as usize;
+                    let program_id = get_account_key_data_fn(program_index)
                         .ok_or::<ProgramError>(AccountResolutionError::AccountNotFound.into())?
-                        .0
+                        .0;
+                    (Some(program_index), program_id)
                 };
-                let seeds = Seed::unpack_address_config(&self.address_config)?;
-                Ok(AccountMeta {
-                    pubkey: resolve_pda(
-                        &seeds,
-                        instruction_data,
-                        program_id,
-                        get_account_key_data_fn,
-                    )?,
-                    is_signer: self.is_signer.into(),
-                    is_writable: self.is_writable.into(),
-                })
+                let (pda_seeds, num_seeds) = resolve_pda_seeds(
+                    &self.address_config,
+                    instruction_data,
+                    &get_account_key_data_fn,
+                )?;
+                let pda_seeds = &pda_seeds[..num_seeds];
+                if let Some(address_trace) = address_trace {
+                    *address_trace = AddressTrace::Pda {
+                        program_index,
+                        program_id: *program_id,
+                        seeds: Seed::unpack_address_config(&self.address_config)?
+                            .into_iter()
+                            .zip(pda_seeds)
+                            .map(|(seed, bytes)| SeedTrace {
+                                seed,
+                                bytes: bytes.to_vec(),
+                            })
+                            .collect(),
+                    };
+                }
+                Pubkey::find_program_address(pda_seeds, program_id).0
             }
             2 => {
                 let key_data = PubkeyData::unpack(&self.address_config)?;
-                Ok(AccountMeta {
-                    pubkey: resolve_key_data(&key_data, instruction_data, get_account_key_data_fn)?,
-                    is_signer: self.is_signer.into(),
-                    is_writable: self.is_writable.into(),
-                })
+                let pubkey =
+                    resolve_key_data(&key_data, instruction_data, &get_account_key_data_fn)?;
+                if let Some(address_trace) = address_trace {
+                    *address_trace = AddressTrace::PubkeyData {
+                        key_data,
+                        bytes: pubkey.to_bytes().to_vec(),
+                    };
+                }
+                pubkey
             }
-            _ => Err(ProgramError::InvalidAccountData),
-        }
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(AccountMeta {
+            pubkey,
+            is_signer: self.is_signer.into(),
+            is_writable: self.is_writable.into(),
+        })
+    }
+
+    /// Get all of the accounts needed to resolve this `ExtraAccountMeta`, for
+    /// their key or their data
+    pub(crate) fn account_refs(
+        &self,
+    ) -> Result<impl Iterator<Item = AccountRef>, ExtraAccountMetaIssueKind> {
+        let (program_ref, seeds, key_data_ref) = match self.discriminator {
+            0 => (None, vec![], None),
+            x if x == 1 || x >= U8_TOP_BIT => {
+                let program_ref = (x >= U8_TOP_BIT).then(|| AccountRef {
+                    index: x.saturating_sub(U8_TOP_BIT) as usize,
+                    uses_data: false,
+                });
+                let seeds = Seed::unpack_address_config(&self.address_config)
+                    .map_err(|_| ExtraAccountMetaIssueKind::InvalidAddressConfig)?;
+                (program_ref, seeds, None)
+            }
+            2 => match PubkeyData::unpack(&self.address_config) {
+                Ok(PubkeyData::InstructionData { .. }) => (None, vec![], None),
+                Ok(PubkeyData::AccountData { account_index, .. }) => (
+                    None,
+                    vec![],
+                    Some(AccountRef {
+                        index: account_index as usize,
+                        uses_data: true,
+                    }),
+                ),
+                Ok(PubkeyData::Uninitialized) | Err(_) => {
+                    return Err(ExtraAccountMetaIssueKind::InvalidAddressConfig)
+                }
+            },
+            discriminator => {
+                return Err(ExtraAccountMetaIssueKind::UnknownDiscriminator { discriminator })
+            }
+        };
+        let seed_refs = seeds.into_iter().filter_map(|seed| match seed {
+            Seed::AccountKey { index } => Some(AccountRef {
+                index: index as usize,
+                uses_data: false,
+            }),
+            Seed::AccountData { account_index, .. } => Some(AccountRef {
+                index: account_index as usize,
+                uses_data: true,
+            }),
+            _ => None,
+        });
+        Ok(program_ref.into_iter().chain(seed_refs).chain(key_data_ref))
     }
 }
 
+/// An account needed to resolve an `ExtraAccountMeta`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct AccountRef {
+    /// The index of the account in the accounts list
+    pub index: usize,
+    /// Whether the data of the account is needed, rather than only its key
+    pub uses_data: bool,
+}
+
 impl From<&AccountMeta> for ExtraAccountMeta {
     fn from(meta: &AccountMeta) -> Self {
         Self {
@@ -73,6 +73,9 @@ pub enum AccountResolutionError {
     /// Tried to pack an invalid pubkey data configuration
     #[error("Tried to pack an invalid pubkey data configuration")]
     InvalidPubkeyDataConfig,
+    /// Extra account metas can never be resolved
+    #[error("Extra account metas can never be resolved")]
+    InvalidExtraAccountMetas,
 }
 
 impl From<AccountResolutionError> for ProgramError {
@@ -149,6 +152,9 @@ impl PrintProgramError for AccountResolutionError {
             AccountResolutionError::InvalidPubkeyDataConfig => {
                 msg!("Tried to pack an invalid pubkey data configuration",)
             }
+            AccountResolutionError::InvalidExtraAccountMetas => {
+                msg!("Extra account metas can never be resolved")
+            }
         }
     }
 }
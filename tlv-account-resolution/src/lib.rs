@@ -9,9 +9,12 @@
 
 pub mod account;
 pub mod error;
+pub mod mismatch;
 pub mod pubkey_data;
 pub mod seeds;
 pub mod state;
+pub mod trace;
+pub mod validation;
 
 // Export current sdk types for downstream users building with a different sdk
 // version
@@ -0,0 +1,117 @@
+//! Type describing an extra required account that doesn't match the
+//! validation data
+//!
+//! See `ExtraAccountMetaList::find_account_mismatch`.
+
+use {
+    solana_instruction::AccountMeta,
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
+    std::{fmt, str::FromStr},
+};
+
+/// Prefix of the program logs written by the runtime for `msg!`
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+/// Prefix of the mismatch description
+const MISMATCH_PREFIX: &str = "Incorrect extra account ";
+
+/// An extra required account that wasn't provided as expected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountMismatch {
+    /// The index of the account in the list of extra accounts
+    pub index: usize,
+    /// The account meta resolved from the validation data
+    pub expected: AccountMeta,
+    /// The account meta provided at the position of the extra account, if
+    /// any
+    pub provided: Option<AccountMeta>,
+}
+
+impl AccountMismatch {
+    /// Whether a different account, or no account, was provided
+    pub fn is_pubkey_mismatch(&self) -> bool {
+        self.provided
+            .as_ref()
+            .is_none_or(|provided| provided.pubkey != self.expected.pubkey)
+    }
+
+    /// Whether the provided account has a different signer privilege
+    pub fn is_signer_mismatch(&self) -> bool {
+        self.provided
+            .as_ref()
+            .is_some_and(|provided| provided.is_signer != self.expected.is_signer)
+    }
+
+    /// Whether the provided account has a different writable privilege
+    pub fn is_writable_mismatch(&self) -> bool {
+        self.provided
+            .as_ref()
+            .is_some_and(|provided| provided.is_writable != self.expected.is_writable)
+    }
+
+    /// Finds the mismatch logged by `ExtraAccountMetaList::check_account_infos`
+    /// in the logs of a failed transaction or simulation
+    pub fn from_logs<S: AsRef<str>>(logs: &[S]) -> Option<Self> {
+        logs.iter().rev().find_map(|log| {
+            let log = log.as_ref();
+            log.strip_prefix(PROGRAM_LOG_PREFIX)
+                .unwrap_or(log)
+                .parse()
+                .ok()
+        })
+    }
+}
+
+/// Formats an account meta as `<pubkey> (signer: <bool>, writable: <bool>)`
+fn fmt_meta(f: &mut fmt::Formatter<'_>, meta: &AccountMeta) -> fmt::Result {
+    write!(
+        f,
+        "{} (signer: {}, writable: {})",
+        meta.pubkey, meta.is_signer, meta.is_writable
+    )
+}
+
+/// Parses an account meta formatted by `fmt_meta`
+fn parse_meta(s: &str) -> Option<AccountMeta> {
+    let (pubkey, privileges) = s.split_once(" (signer: ")?;
+    let (is_signer, is_writable) = privileges.strip_suffix(')')?.split_once(", writable: ")?;
+    Some(AccountMeta {
+        pubkey: Pubkey::from_str(pubkey).ok()?,
+        is_signer: is_signer.parse().ok()?,
+        is_writable: is_writable.parse().ok()?,
+    })
+}
+
+impl fmt::Display for AccountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}: expected ", MISMATCH_PREFIX, self.index)?;
+        fmt_meta(f, &self.expected)?;
+        write!(f, ", provided ")?;
+        match &self.provided {
+            Some(provided) => fmt_meta(f, provided),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl FromStr for AccountMismatch {
+    type Err = ProgramError;
+
+    /// Parses a mismatch formatted with `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (index, rest) = s.strip_prefix(MISMATCH_PREFIX)?.split_once(": expected ")?;
+            let (expected, provided) = rest.split_once(", provided ")?;
+            Some(Self {
+                index: index.parse().ok()?,
+                expected: parse_meta(expected)?,
+                provided: if provided == "none" {
+                    None
+                } else {
+                    Some(parse_meta(provided)?)
+                },
+            })
+        };
+        parse().ok_or(ProgramError::InvalidArgument)
+    }
+}
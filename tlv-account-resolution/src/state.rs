@@ -1,9 +1,16 @@
 //! State transition types
 
 use {
-    crate::{account::ExtraAccountMeta, error::AccountResolutionError},
+    crate::{
+        account::{AccountRef, ExtraAccountMeta},
+        error::AccountResolutionError,
+        mismatch::AccountMismatch,
+        trace::{AddressTrace, ExtraAccountMetaTrace},
+        validation::{ExtraAccountMetaIssue, ExtraAccountMetaIssueKind},
+    },
     solana_account_info::AccountInfo,
     solana_instruction::{AccountMeta, Instruction},
+    solana_msg::msg,
     solana_program_error::ProgramError,
     solana_pubkey::Pubkey,
     spl_discriminator::SplDiscriminate,
@@ -28,14 +35,62 @@ fn account_info_to_meta(account_info: &AccountInfo) -> AccountMeta {
     }
 }
 
+/// Fetch the data of all of the given accounts with one call to a batch
+/// fetching function
+async fn fetch_account_datas<F, Fut>(
+    fetch_multiple_accounts_data_fn: &F,
+    pubkeys: Vec<Pubkey>,
+) -> Result<Vec<Option<Vec<u8>>>, ProgramError>
+where
+    F: Fn(Vec<Pubkey>) -> Fut,
+    Fut: Future<Output = Vec<AccountDataResult>>,
+{
+    let num_accounts = pubkeys.len();
+    let results = fetch_multiple_accounts_data_fn(pubkeys).await;
+    if results.len() != num_accounts {
+        return Err(AccountResolutionError::AccountFetchFailed.into());
+    }
+    results
+        .into_iter()
+        .map(|result| result.map_err(|_| AccountResolutionError::AccountFetchFailed.into()))
+        .collect()
+}
+
+/// Fetch the data of every account from `fetched_len` on with one call to a
+/// batch fetching function, then mark all of them as fetched
+async fn fetch_pending_account_datas<F, Fut>(
+    fetch_multiple_accounts_data_fn: &F,
+    account_key_datas: &mut [(Pubkey, Option<Vec<u8>>)],
+    fetched_len: &mut usize,
+) -> Result<(), ProgramError>
+where
+    F: Fn(Vec<Pubkey>) -> Fut,
+    Fut: Future<Output = Vec<AccountDataResult>>,
+{
+    let pending = &mut account_key_datas[*fetched_len..];
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let pubkeys = pending.iter().map(|(pubkey, _)| *pubkey).collect();
+    let datas = fetch_account_datas(fetch_multiple_accounts_data_fn, pubkeys).await?;
+    for ((_, account_data), data) in pending.iter_mut().zip(datas) {
+        *account_data = data;
+    }
+    *fetched_len = account_key_datas.len();
+    Ok(())
+}
+
 /// De-escalate an account meta if necessary
-fn de_escalate_account_meta(account_meta: &mut AccountMeta, account_metas: &[AccountMeta]) {
+fn de_escalate_account_meta<'a>(
+    account_meta: &mut AccountMeta,
+    account_metas: impl IntoIterator<Item = &'a AccountMeta>,
+) {
     // This is a little tricky to read, but the idea is to see if
     // this account is marked as writable or signer anywhere in
     // the instruction at the start. If so, DON'T escalate it to
     // be a writer or signer in the CPI
     let maybe_highest_privileges = account_metas
-        .iter()
+        .into_iter()
         .filter(|&x| x.pubkey == account_meta.pubkey)
         .map(|x| (x.is_signer, x.is_writable))
         .reduce(|acc, x| (acc.0 || x.0, acc.1 || x.1));
@@ -179,6 +234,69 @@ impl ExtraAccountMetaList {
         Ok(())
     }
 
+    /// Initialize pod slice data for the given instruction and its required
+    /// list of `ExtraAccountMeta`s, like `init`, after checking with
+    /// `validate` that the list can be resolved for an instruction with
+    /// `base_account_count` accounts.
+    ///
+    /// Every issue found is logged before failing.
+    pub fn init_validated<T: SplDiscriminate>(
+        data: &mut [u8],
+        extra_account_metas: &[ExtraAccountMeta],
+        base_account_count: usize,
+    ) -> Result<(), ProgramError> {
+        let issues = Self::validate(extra_account_metas, base_account_count);
+        if !issues.is_empty() {
+            for issue in issues {
+                msg!("{}", issue);
+            }
+            return Err(AccountResolutionError::InvalidExtraAccountMetas.into());
+        }
+        Self::init::<T>(data, extra_account_metas)
+    }
+
+    /// Check that a list of `ExtraAccountMeta`s can be resolved for an
+    /// instruction with `base_account_count` accounts, before any extra
+    /// accounts.
+    ///
+    /// Returns every unknown discriminator, invalid address config, and
+    /// account index pointing past all accounts or to an account that isn't
+    /// resolved yet. Instruction data indices can't be checked without the
+    /// instruction data.
+    pub fn validate(
+        extra_account_metas: &[ExtraAccountMeta],
+        base_account_count: usize,
+    ) -> Vec<ExtraAccountMetaIssue> {
+        let total_account_count = base_account_count.saturating_add(extra_account_metas.len());
+        let mut issues = vec![];
+        for (index, extra_meta) in extra_account_metas.iter().enumerate() {
+            let account_refs = match extra_meta.account_refs() {
+                Ok(account_refs) => account_refs,
+                Err(kind) => {
+                    issues.push(ExtraAccountMetaIssue { index, kind });
+                    continue;
+                }
+            };
+            // Only the accounts before this one are resolved
+            let resolved_account_count = base_account_count.saturating_add(index);
+            for AccountRef {
+                index: account_index,
+                ..
+            } in account_refs
+            {
+                let kind = if account_index >= total_account_count {
+                    ExtraAccountMetaIssueKind::AccountIndexOutOfRange { account_index }
+                } else if account_index >= resolved_account_count {
+                    ExtraAccountMetaIssueKind::ForwardReference { account_index }
+                } else {
+                    continue;
+                };
+                issues.push(ExtraAccountMetaIssue { index, kind });
+            }
+        }
+        issues
+    }
+
     /// Update pod slice data for the given instruction and its required
     /// list of `ExtraAccountMeta`s
     pub fn update<T: SplDiscriminate>(
@@ -218,55 +336,76 @@ impl ExtraAccountMetaList {
     /// if necessary.
     ///
     /// Note: this function will also verify all extra required accounts
-    /// have been provided in the correct order
+    /// have been provided in the correct order, and logs the first incorrect
+    /// one, which can be recovered with `AccountMismatch::from_logs`
     pub fn check_account_infos<T: SplDiscriminate>(
         account_infos: &[AccountInfo],
         instruction_data: &[u8],
         program_id: &Pubkey,
         data: &[u8],
     ) -> Result<(), ProgramError> {
+        if let Some(mismatch) =
+            Self::find_account_mismatch::<T>(account_infos, instruction_data, program_id, data)?
+        {
+            msg!("{}", mismatch);
+            return Err(AccountResolutionError::IncorrectAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Checks provided account infos against validation data like
+    /// `check_account_infos`, returning the first extra account that wasn't
+    /// provided as expected
+    pub fn find_account_mismatch<T: SplDiscriminate>(
+        account_infos: &[AccountInfo],
+        instruction_data: &[u8],
+        program_id: &Pubkey,
+        data: &[u8],
+    ) -> Result<Option<AccountMismatch>, ProgramError> {
         let state = TlvStateBorrowed::unpack(data).unwrap();
         let extra_meta_list = ExtraAccountMetaList::unpack_with_tlv_state::<T>(&state)?;
         let extra_account_metas = extra_meta_list.data();
 
         let initial_accounts_len = account_infos.len() - extra_account_metas.len();
+        // Nothing to check, so don't borrow accounts that may already be
+        // borrowed elsewhere
+        if extra_account_metas.is_empty() {
+            return Ok(None);
+        }
 
-        // Convert to `AccountMeta` to check resolved metas
-        let provided_metas = account_infos
-            .iter()
-            .map(account_info_to_meta)
-            .collect::<Vec<_>>();
+        // Borrow the data of every account once, so we can reference it in the
+        // resolution step of every extra account
+        let mut account_key_data_refs = Vec::with_capacity(account_infos.len());
+        for info in account_infos {
+            account_key_data_refs.push((info.key, info.try_borrow_data()?));
+        }
 
         for (i, config) in extra_account_metas.iter().enumerate() {
-            let meta = {
-                // Create a list of `Ref`s so we can reference account data in the
-                // resolution step
-                let account_key_data_refs = account_infos
-                    .iter()
-                    .map(|info| {
-                        let key = *info.key;
-                        let data = info.try_borrow_data()?;
-                        Ok((key, data))
-                    })
-                    .collect::<Result<Vec<_>, ProgramError>>()?;
-
-                config.resolve(instruction_data, program_id, |usize| {
-                    account_key_data_refs
-                        .get(usize)
-                        .map(|(pubkey, opt_data)| (pubkey, Some(opt_data.as_ref())))
-                })?
-            };
+            let meta = config.resolve(instruction_data, program_id, |usize| {
+                account_key_data_refs
+                    .get(usize)
+                    .map(|(pubkey, data)| (*pubkey, Some(data.as_ref())))
+            })?;
 
             // Ensure the account is in the correct position
             let expected_index = i
                 .checked_add(initial_accounts_len)
                 .ok_or::<ProgramError>(AccountResolutionError::CalculationFailure.into())?;
-            if provided_metas.get(expected_index) != Some(&meta) {
-                return Err(AccountResolutionError::IncorrectAccount.into());
+            let provided = account_infos.get(expected_index);
+            if !provided.is_some_and(|info| {
+                *info.key == meta.pubkey
+                    && info.is_signer == meta.is_signer
+                    && info.is_writable == meta.is_writable
+            }) {
+                return Ok(Some(AccountMismatch {
+                    index: i,
+                    expected: meta,
+                    provided: provided.map(account_info_to_meta),
+                }));
             }
         }
 
-        Ok(())
+        Ok(None)
     }
 
     /// Add the additional account metas to an existing instruction
@@ -278,41 +417,202 @@ impl ExtraAccountMetaList {
     where
         F: Fn(Pubkey) -> Fut,
         Fut: Future<Output = AccountDataResult>,
+    {
+        Self::resolve_account_metas_one_by_one::<T, F, Fut>(
+            &instruction.program_id,
+            &instruction.data,
+            &mut instruction.accounts,
+            fetch_account_data_fn,
+            data,
+            None,
+        )
+        .await
+    }
+
+    /// Explain how `add_to_instruction` resolves the additional account metas
+    /// for an instruction, without modifying it.
+    ///
+    /// Returns a trace for every additional account, with the seed bytes or
+    /// pubkey data used to resolve its address, and any de-escalation of its
+    /// privileges.
+    pub async fn explain<T: SplDiscriminate, F, Fut>(
+        instruction: &Instruction,
+        fetch_account_data_fn: F,
+        data: &[u8],
+    ) -> Result<Vec<ExtraAccountMetaTrace>, ProgramError>
+    where
+        F: Fn(Pubkey) -> Fut,
+        Fut: Future<Output = AccountDataResult>,
+    {
+        let mut accounts = instruction.accounts.clone();
+        let mut traces = vec![];
+        Self::resolve_account_metas_one_by_one::<T, F, Fut>(
+            &instruction.program_id,
+            &instruction.data,
+            &mut accounts,
+            fetch_account_data_fn,
+            data,
+            Some(&mut traces),
+        )
+        .await?;
+        Ok(traces)
+    }
+
+    /// Add the additional account metas to an existing instruction, fetching
+    /// account data in batches, such as with `getMultipleAccounts`.
+    ///
+    /// The data of all instruction accounts is fetched in one call. The data
+    /// of the additional accounts is only fetched when a later additional
+    /// account needs it to be resolved, in one call for all of the accounts
+    /// resolved since the previous fetch.
+    ///
+    /// The batch fetching function must return one result per requested
+    /// account, in the same order.
+    pub async fn add_to_instruction_batched<T: SplDiscriminate, F, Fut>(
+        instruction: &mut Instruction,
+        fetch_multiple_accounts_data_fn: F,
+        data: &[u8],
+    ) -> Result<(), ProgramError>
+    where
+        F: Fn(Vec<Pubkey>) -> Fut,
+        Fut: Future<Output = Vec<AccountDataResult>>,
+    {
+        Self::resolve_account_metas::<T, F, Fut>(
+            &instruction.program_id,
+            &instruction.data,
+            &mut instruction.accounts,
+            fetch_multiple_accounts_data_fn,
+            false,
+            data,
+            None,
+        )
+        .await
+    }
+
+    /// Resolve the additional account metas like `resolve_account_metas`,
+    /// fetching the data of every account on its own, as soon as it's added
+    async fn resolve_account_metas_one_by_one<T: SplDiscriminate, F, Fut>(
+        program_id: &Pubkey,
+        instruction_data: &[u8],
+        accounts: &mut Vec<AccountMeta>,
+        fetch_account_data_fn: F,
+        data: &[u8],
+        traces: Option<&mut Vec<ExtraAccountMetaTrace>>,
+    ) -> Result<(), ProgramError>
+    where
+        F: Fn(Pubkey) -> Fut,
+        Fut: Future<Output = AccountDataResult>,
+    {
+        let fetch_account_data_fn = &fetch_account_data_fn;
+        Self::resolve_account_metas::<T, _, _>(
+            program_id,
+            instruction_data,
+            accounts,
+            |pubkeys: Vec<Pubkey>| async move {
+                let mut results = Vec::with_capacity(pubkeys.len());
+                for pubkey in pubkeys {
+                    results.push(fetch_account_data_fn(pubkey).await);
+                }
+                results
+            },
+            true,
+            data,
+            traces,
+        )
+        .await
+    }
+
+    /// Resolve the additional account metas for an instruction, adding them
+    /// to its accounts, and adding a trace of every resolution to `traces`,
+    /// if given.
+    ///
+    /// The data of all instruction accounts is fetched first. If
+    /// `fetch_eagerly` is set, the data of every additional account is
+    /// fetched as soon as it's resolved. Otherwise, it's only fetched when a
+    /// later additional account needs it, along with all of the other
+    /// accounts resolved since the previous fetch.
+    async fn resolve_account_metas<T: SplDiscriminate, F, Fut>(
+        program_id: &Pubkey,
+        instruction_data: &[u8],
+        accounts: &mut Vec<AccountMeta>,
+        fetch_multiple_accounts_data_fn: F,
+        fetch_eagerly: bool,
+        data: &[u8],
+        mut traces: Option<&mut Vec<ExtraAccountMetaTrace>>,
+    ) -> Result<(), ProgramError>
+    where
+        F: Fn(Vec<Pubkey>) -> Fut,
+        Fut: Future<Output = Vec<AccountDataResult>>,
     {
         let state = TlvStateBorrowed::unpack(data)?;
         let bytes = state.get_first_bytes::<T>()?;
         let extra_account_metas = PodSlice::<ExtraAccountMeta>::unpack(bytes)?;
 
-        // Fetch account data for each of the instruction accounts
-        let mut account_key_datas = vec![];
-        for meta in instruction.accounts.iter() {
-            let account_data = fetch_account_data_fn(meta.pubkey)
-                .await
-                .map_err::<ProgramError, _>(|_| {
-                    AccountResolutionError::AccountFetchFailed.into()
-                })?;
-            account_key_datas.push((meta.pubkey, account_data));
-        }
+        let mut account_key_datas = accounts
+            .iter()
+            .map(|meta| (meta.pubkey, None))
+            .collect::<Vec<_>>();
+        // Accounts from this index on have been resolved, but not fetched
+        let mut fetched_len = 0;
+        fetch_pending_account_datas(
+            &fetch_multiple_accounts_data_fn,
+            &mut account_key_datas,
+            &mut fetched_len,
+        )
+        .await?;
+
+        for (index, extra_meta) in extra_account_metas.data().iter().enumerate() {
+            // Fetch the pending accounts if this one needs any of their data.
+            // Invalid configurations fetch nothing, and fail to resolve below.
+            if extra_meta.account_refs().is_ok_and(|mut account_refs| {
+                account_refs.any(|account_ref| {
+                    account_ref.uses_data
+                        && account_ref.index >= fetched_len
+                        && account_ref.index < account_key_datas.len()
+                })
+            }) {
+                fetch_pending_account_datas(
+                    &fetch_multiple_accounts_data_fn,
+                    &mut account_key_datas,
+                    &mut fetched_len,
+                )
+                .await?;
+            }
 
-        for extra_meta in extra_account_metas.data().iter() {
-            let mut meta =
-                extra_meta.resolve(&instruction.data, &instruction.program_id, |usize| {
+            let mut address = traces.is_some().then_some(AddressTrace::Pubkey);
+            let resolved_meta = extra_meta.resolve_traced(
+                instruction_data,
+                program_id,
+                |usize| {
                     account_key_datas
                         .get(usize)
                         .map(|(pubkey, opt_data)| (pubkey, opt_data.as_ref().map(|x| x.as_slice())))
-                })?;
-            de_escalate_account_meta(&mut meta, &instruction.accounts);
-
-            // Fetch account data for the new account
-            account_key_datas.push((
-                meta.pubkey,
-                fetch_account_data_fn(meta.pubkey)
-                    .await
-                    .map_err::<ProgramError, _>(|_| {
-                        AccountResolutionError::AccountFetchFailed.into()
-                    })?,
-            ));
-            instruction.accounts.push(meta);
+                },
+                address.as_mut(),
+            )?;
+            let mut meta = resolved_meta.clone();
+            de_escalate_account_meta(&mut meta, accounts.iter());
+
+            account_key_datas.push((meta.pubkey, None));
+            if fetch_eagerly {
+                fetch_pending_account_datas(
+                    &fetch_multiple_accounts_data_fn,
+                    &mut account_key_datas,
+                    &mut fetched_len,
+                )
+                .await?;
+            }
+            if let (Some(traces), Some(address)) = (traces.as_deref_mut(), address) {
+                traces.push(ExtraAccountMetaTrace {
+                    index,
+                    account_index: accounts.len(),
+                    extra_account_meta: *extra_meta,
+                    address,
+                    resolved_meta,
+                    meta: meta.clone(),
+                });
+            }
+            accounts.push(meta);
         }
         Ok(())
     }
@@ -327,41 +627,53 @@ impl ExtraAccountMetaList {
         let state = TlvStateBorrowed::unpack(data)?;
         let bytes = state.get_first_bytes::<T>()?;
         let extra_account_metas = PodSlice::<ExtraAccountMeta>::unpack(bytes)?;
+        let extra_account_metas = extra_account_metas.data();
+        // Nothing to add, so don't borrow accounts that may already be
+        // borrowed elsewhere
+        if extra_account_metas.is_empty() {
+            return Ok(());
+        }
 
-        for extra_meta in extra_account_metas.data().iter() {
-            let mut meta = {
-                // Create a list of `Ref`s so we can reference account data in the
-                // resolution step
-                let account_key_data_refs = cpi_account_infos
-                    .iter()
-                    .map(|info| {
-                        let key = *info.key;
-                        let data = info.try_borrow_data()?;
-                        Ok((key, data))
-                    })
-                    .collect::<Result<Vec<_>, ProgramError>>()?;
-
-                extra_meta.resolve(
-                    &cpi_instruction.data,
-                    &cpi_instruction.program_id,
-                    |usize| {
-                        account_key_data_refs
-                            .get(usize)
-                            .map(|(pubkey, opt_data)| (pubkey, Some(opt_data.as_ref())))
-                    },
-                )?
-            };
-            de_escalate_account_meta(&mut meta, &cpi_instruction.accounts);
+        // Borrow the data of every account once, and add the data of every
+        // new account, so we can reference it in the resolution step of every
+        // extra account
+        let mut account_key_data_refs =
+            Vec::with_capacity(cpi_account_infos.len() + extra_account_metas.len());
+        for info in cpi_account_infos.iter() {
+            account_key_data_refs.push((info.key, info.try_borrow_data()?));
+        }
+        // The new metas and account infos are only added to the CPI once all
+        // of them are resolved, so that they're never out of sync on failure
+        let mut new_metas = Vec::with_capacity(extra_account_metas.len());
+        let mut new_account_infos = Vec::with_capacity(extra_account_metas.len());
+
+        for extra_meta in extra_account_metas {
+            let mut meta = extra_meta.resolve(
+                &cpi_instruction.data,
+                &cpi_instruction.program_id,
+                |usize| {
+                    account_key_data_refs
+                        .get(usize)
+                        .map(|(pubkey, data)| (*pubkey, Some(data.as_ref())))
+                },
+            )?;
+            de_escalate_account_meta(&mut meta, cpi_instruction.accounts.iter().chain(&new_metas));
 
             let account_info = account_infos
                 .iter()
                 .find(|&x| *x.key == meta.pubkey)
-                .ok_or(AccountResolutionError::IncorrectAccount)?
-                .clone();
+                .ok_or(AccountResolutionError::IncorrectAccount)?;
 
-            cpi_instruction.accounts.push(meta);
-            cpi_account_infos.push(account_info);
+            account_key_data_refs.push((account_info.key, account_info.try_borrow_data()?));
+            new_metas.push(meta);
+            new_account_infos.push(account_info);
         }
+
+        // The new account infos can only be cloned once their data isn't
+        // borrowed anymore
+        drop(account_key_data_refs);
+        cpi_instruction.accounts.extend(new_metas);
+        cpi_account_infos.extend(new_account_infos.into_iter().cloned());
         Ok(())
     }
 }
@@ -370,7 +682,11 @@ impl ExtraAccountMetaList {
 mod tests {
     use {
         super::*,
-        crate::{pubkey_data::PubkeyData, seeds::Seed},
+        crate::{
+            pubkey_data::PubkeyData,
+            seeds::Seed,
+            trace::{AddressTrace, SeedTrace},
+        },
         solana_instruction::AccountMeta,
         solana_program_test::tokio,
         solana_pubkey::Pubkey,
@@ -408,6 +724,17 @@ mod tests {
                 .get(&pubkey)
                 .map(|account| account.try_borrow_data().unwrap().to_vec()))
         }
+
+        pub async fn get_multiple_accounts_data(
+            &self,
+            pubkeys: Vec<Pubkey>,
+        ) -> Vec<AccountDataResult> {
+            let mut results = vec![];
+            for pubkey in pubkeys {
+                results.push(self.get_account_data(pubkey).await);
+            }
+            results
+        }
     }
 
     #[tokio::test]
@@ -1414,6 +1741,26 @@ mod tests {
         messed_account_infos.swap(5, 6);
         messed_account_infos.swap(8, 7);
 
+        // Missing one of the extra account infos fails without adding any of
+        // the resolved accounts
+        let missing_account_infos = messed_account_infos
+            .iter()
+            .filter(|info| *info.key != extra_meta3.pubkey)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ExtraAccountMetaList::add_to_cpi_instruction::<TestInstruction>(
+                &mut cpi_instruction,
+                &mut cpi_account_infos,
+                &buffer,
+                &missing_account_infos,
+            )
+            .unwrap_err(),
+            AccountResolutionError::IncorrectAccount.into()
+        );
+        assert_eq!(cpi_instruction.accounts.len(), 2);
+        assert_eq!(cpi_account_infos.len(), 2);
+
         // Resolve the rest!
         ExtraAccountMetaList::add_to_cpi_instruction::<TestInstruction>(
             &mut cpi_instruction,
@@ -1480,6 +1827,386 @@ mod tests {
         assert_eq!(instruction.accounts, check_metas,);
     }
 
+    #[test]
+    fn validate_extra_account_metas() {
+        let metas = [
+            AccountMeta::new(Pubkey::new_unique(), false).into(),
+            // the second seed refers to this account
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::AccountKey { index: 0 },
+                    Seed::AccountData {
+                        account_index: 3,
+                        data_index: 0,
+                        length: 1,
+                    },
+                ],
+                false,
+                true,
+            )
+            .unwrap(),
+            // the program index is past all accounts
+            ExtraAccountMeta::new_external_pda_with_seeds(
+                9,
+                &[Seed::AccountKey { index: 2 }],
+                false,
+                false,
+            )
+            .unwrap(),
+            // refers to a later account
+            ExtraAccountMeta::new_with_pubkey_data(
+                &PubkeyData::AccountData {
+                    account_index: 6,
+                    data_index: 0,
+                },
+                false,
+                false,
+            )
+            .unwrap(),
+            ExtraAccountMeta {
+                discriminator: 5,
+                ..Default::default()
+            },
+            ExtraAccountMeta {
+                discriminator: 2,
+                ..Default::default()
+            },
+        ];
+
+        let issues = ExtraAccountMetaList::validate(&metas, 2);
+        assert_eq!(
+            issues,
+            vec![
+                ExtraAccountMetaIssue {
+                    index: 1,
+                    kind: ExtraAccountMetaIssueKind::ForwardReference { account_index: 3 },
+                },
+                ExtraAccountMetaIssue {
+                    index: 2,
+                    kind: ExtraAccountMetaIssueKind::AccountIndexOutOfRange { account_index: 9 },
+                },
+                ExtraAccountMetaIssue {
+                    index: 3,
+                    kind: ExtraAccountMetaIssueKind::ForwardReference { account_index: 6 },
+                },
+                ExtraAccountMetaIssue {
+                    index: 4,
+                    kind: ExtraAccountMetaIssueKind::UnknownDiscriminator { discriminator: 5 },
+                },
+                ExtraAccountMetaIssue {
+                    index: 5,
+                    kind: ExtraAccountMetaIssueKind::InvalidAddressConfig,
+                },
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "Extra account 1: account index 3 is not resolved yet"
+        );
+
+        let account_size = ExtraAccountMetaList::size_of(metas.len()).unwrap();
+        let mut buffer = vec![0; account_size];
+        assert_eq!(
+            ExtraAccountMetaList::init_validated::<TestInstruction>(&mut buffer, &metas, 2)
+                .unwrap_err(),
+            AccountResolutionError::InvalidExtraAccountMetas.into()
+        );
+        assert!(buffer.iter().all(|&x| x == 0));
+
+        // with more instruction accounts, the first ones are fine
+        assert!(ExtraAccountMetaList::validate(&metas[..3], 10).is_empty());
+        let account_size = ExtraAccountMetaList::size_of(3).unwrap();
+        let mut buffer = vec![0; account_size];
+        ExtraAccountMetaList::init_validated::<TestInstruction>(&mut buffer, &metas[..3], 10)
+            .unwrap();
+        let state = TlvStateBorrowed::unpack(&buffer).unwrap();
+        let unpacked_metas =
+            ExtraAccountMetaList::unpack_with_tlv_state::<TestInstruction>(&state).unwrap();
+        assert_eq!(unpacked_metas.data(), &metas[..3]);
+    }
+
+    #[tokio::test]
+    async fn explain_resolution() {
+        let program_id = Pubkey::new_unique();
+
+        let pubkey_a = Pubkey::new_unique();
+        let mut lamports_a = 0;
+        let mut data_a = [1, 2, 3, 4];
+        let owner = Pubkey::new_unique();
+        let account_infos = [AccountInfo::new(
+            &pubkey_a,
+            false,
+            true,
+            &mut lamports_a,
+            &mut data_a,
+            &owner,
+            false,
+            0,
+        )];
+        let pubkey_b = Pubkey::new_unique();
+
+        let pda_seeds = [
+            Seed::Literal {
+                bytes: b"seed".to_vec(),
+            },
+            Seed::InstructionData {
+                index: 0,
+                length: 2,
+            },
+            Seed::AccountKey { index: 0 },
+            Seed::AccountData {
+                account_index: 0,
+                data_index: 1,
+                length: 2,
+            },
+        ];
+        let metas = [
+            // escalates the privileges of an instruction account
+            ExtraAccountMeta::new_with_pubkey(&pubkey_b, true, true).unwrap(),
+            ExtraAccountMeta::new_with_seeds(&pda_seeds, false, true).unwrap(),
+            ExtraAccountMeta::new_external_pda_with_seeds(
+                1,
+                &[Seed::AccountKey { index: 2 }],
+                false,
+                false,
+            )
+            .unwrap(),
+            ExtraAccountMeta::new_with_pubkey_data(
+                &PubkeyData::InstructionData { index: 2 },
+                false,
+                false,
+            )
+            .unwrap(),
+        ];
+
+        let account_size = ExtraAccountMetaList::size_of(metas.len()).unwrap();
+        let mut buffer = vec![0; account_size];
+        ExtraAccountMetaList::init::<TestInstruction>(&mut buffer, &metas).unwrap();
+
+        let mock_rpc = MockRpc::setup(&account_infos);
+
+        let pubkey_d = Pubkey::new_unique();
+        let mut ix_data = vec![7, 8];
+        ix_data.extend_from_slice(pubkey_d.as_ref());
+        let ix_accounts = vec![
+            AccountMeta::new(pubkey_a, false),
+            AccountMeta::new_readonly(pubkey_b, false),
+        ];
+        let instruction = Instruction::new_with_bytes(program_id, &ix_data, ix_accounts);
+
+        let traces = ExtraAccountMetaList::explain::<TestInstruction, _, _>(
+            &instruction,
+            |pubkey| mock_rpc.get_account_data(pubkey),
+            &buffer,
+        )
+        .await
+        .unwrap();
+
+        // the instruction is left as is, and the traces match what's added
+        let mut check_instruction = instruction.clone();
+        ExtraAccountMetaList::add_to_instruction::<TestInstruction, _, _>(
+            &mut check_instruction,
+            |pubkey| mock_rpc.get_account_data(pubkey),
+            &buffer,
+        )
+        .await
+        .unwrap();
+        assert_eq!(instruction.accounts.len(), 2);
+        assert_eq!(
+            traces.iter().map(|t| t.meta.clone()).collect::<Vec<_>>(),
+            check_instruction.accounts[2..]
+        );
+        assert_eq!(
+            traces.iter().map(|t| t.account_index).collect::<Vec<_>>(),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(
+            traces
+                .iter()
+                .map(|t| t.extra_account_meta)
+                .collect::<Vec<_>>(),
+            metas
+        );
+
+        assert_eq!(traces[0].address, AddressTrace::Pubkey);
+        assert_eq!(traces[0].resolved_meta, AccountMeta::new(pubkey_b, true));
+        assert_eq!(traces[0].meta, AccountMeta::new_readonly(pubkey_b, false));
+        assert!(traces[0].is_signer_de_escalated());
+        assert!(traces[0].is_writable_de_escalated());
+
+        let check_pda = Pubkey::find_program_address(
+            &[b"seed", &[7, 8], pubkey_a.as_ref(), &[2, 3]],
+            &program_id,
+        )
+        .0;
+        assert_eq!(
+            traces[1].address,
+            AddressTrace::Pda {
+                program_index: None,
+                program_id,
+                seeds: pda_seeds
+                    .iter()
+                    .cloned()
+                    .zip([
+                        b"seed".to_vec(),
+                        vec![7, 8],
+                        pubkey_a.to_bytes().to_vec(),
+                        vec![2, 3],
+                    ])
+                    .map(|(seed, bytes)| SeedTrace { seed, bytes })
+                    .collect(),
+            }
+        );
+        assert_eq!(traces[1].meta, AccountMeta::new(check_pda, false));
+        assert!(!traces[1].is_writable_de_escalated());
+
+        let check_external_pda = Pubkey::find_program_address(&[pubkey_b.as_ref()], &pubkey_b).0;
+        assert_eq!(
+            traces[2].address,
+            AddressTrace::Pda {
+                program_index: Some(1),
+                program_id: pubkey_b,
+                seeds: vec![SeedTrace {
+                    seed: Seed::AccountKey { index: 2 },
+                    bytes: pubkey_b.to_bytes().to_vec(),
+                }],
+            }
+        );
+        assert_eq!(traces[2].meta.pubkey, check_external_pda);
+
+        assert_eq!(
+            traces[3].address,
+            AddressTrace::PubkeyData {
+                key_data: PubkeyData::InstructionData { index: 2 },
+                bytes: pubkey_d.to_bytes().to_vec(),
+            }
+        );
+        assert_eq!(traces[3].meta.pubkey, pubkey_d);
+    }
+
+    #[tokio::test]
+    async fn batched_fetch() {
+        let program_id = Pubkey::new_unique();
+
+        let pubkey_b = Pubkey::new_unique();
+        let pubkey_a = Pubkey::new_unique();
+        let mut lamports_a = 0;
+        let mut data_a = pubkey_b.to_bytes();
+        let mut lamports_b = 0;
+        let mut data_b = [1, 2, 3, 4, 5, 6, 7, 8];
+        let owner = Pubkey::new_unique();
+        let account_infos = [
+            AccountInfo::new(
+                &pubkey_a,
+                false,
+                true,
+                &mut lamports_a,
+                &mut data_a,
+                &owner,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &pubkey_b,
+                false,
+                false,
+                &mut lamports_b,
+                &mut data_b,
+                &owner,
+                false,
+                0,
+            ),
+        ];
+
+        let extra_meta_c = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+        let metas = [
+            // only needs the data of the instruction account
+            ExtraAccountMeta::new_with_pubkey_data(
+                &PubkeyData::AccountData {
+                    account_index: 0,
+                    data_index: 0,
+                },
+                false,
+                false,
+            )
+            .unwrap(),
+            ExtraAccountMeta::from(&extra_meta_c),
+            // needs the data of the first extra account
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::AccountData {
+                        account_index: 1,
+                        data_index: 2,
+                        length: 4,
+                    },
+                    Seed::AccountKey { index: 2 },
+                ],
+                false,
+                true,
+            )
+            .unwrap(),
+            // only needs keys
+            ExtraAccountMeta::new_with_seeds(&[Seed::AccountKey { index: 3 }], false, false)
+                .unwrap(),
+        ];
+
+        let account_size = ExtraAccountMetaList::size_of(metas.len()).unwrap();
+        let mut buffer = vec![0; account_size];
+        ExtraAccountMetaList::init::<TestInstruction>(&mut buffer, &metas).unwrap();
+
+        let mock_rpc = MockRpc::setup(&account_infos);
+
+        let ix_accounts = vec![AccountMeta::new(pubkey_a, false)];
+        let mut instruction = Instruction::new_with_bytes(program_id, &[], ix_accounts.clone());
+        ExtraAccountMetaList::add_to_instruction::<TestInstruction, _, _>(
+            &mut instruction,
+            |pubkey| mock_rpc.get_account_data(pubkey),
+            &buffer,
+        )
+        .await
+        .unwrap();
+
+        let calls = std::cell::RefCell::new(vec![]);
+        let mut batched_instruction =
+            Instruction::new_with_bytes(program_id, &[], ix_accounts.clone());
+        ExtraAccountMetaList::add_to_instruction_batched::<TestInstruction, _, _>(
+            &mut batched_instruction,
+            |pubkeys| {
+                calls.borrow_mut().push(pubkeys.clone());
+                mock_rpc.get_multiple_accounts_data(pubkeys)
+            },
+            &buffer,
+        )
+        .await
+        .unwrap();
+
+        let check_pda = Pubkey::find_program_address(
+            &[&[3, 4, 5, 6], extra_meta_c.pubkey.as_ref()],
+            &program_id,
+        )
+        .0;
+        assert_eq!(batched_instruction.accounts, instruction.accounts);
+        assert_eq!(batched_instruction.accounts[1].pubkey, pubkey_b);
+        assert_eq!(batched_instruction.accounts[3].pubkey, check_pda);
+        assert_eq!(
+            calls.into_inner(),
+            vec![vec![pubkey_a], vec![pubkey_b, extra_meta_c.pubkey]]
+        );
+
+        // the batch fetcher must return one result per account
+        let mut instruction = Instruction::new_with_bytes(program_id, &[], ix_accounts);
+        assert_eq!(
+            ExtraAccountMetaList::add_to_instruction_batched::<TestInstruction, _, _>(
+                &mut instruction,
+                |_| async { vec![] },
+                &buffer,
+            )
+            .await
+            .unwrap_err(),
+            AccountResolutionError::AccountFetchFailed.into()
+        );
+    }
+
     #[tokio::test]
     async fn update_extra_account_meta_list() {
         let program_id = Pubkey::new_unique();
@@ -1559,6 +2286,50 @@ mod tests {
         update_and_assert_metas(program_id, &mut buffer, &updated_metas_4, &check_metas_4).await;
     }
 
+    #[test]
+    fn empty_list_leaves_accounts_unborrowed() {
+        let program_id = Pubkey::new_unique();
+        let mut buffer = vec![0; ExtraAccountMetaList::size_of(0).unwrap()];
+        ExtraAccountMetaList::init::<TestInstruction>(&mut buffer, &[]).unwrap();
+
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = [0; 8];
+        let account_info = AccountInfo::new(
+            &pubkey,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        let _borrowed = account_info.try_borrow_mut_data().unwrap();
+
+        ExtraAccountMetaList::check_account_infos::<TestInstruction>(
+            &[account_info.clone()],
+            &[],
+            &program_id,
+            &buffer,
+        )
+        .unwrap();
+
+        let mut cpi_instruction =
+            Instruction::new_with_bytes(program_id, &[], vec![AccountMeta::new(pubkey, false)]);
+        let mut cpi_account_infos = vec![account_info.clone()];
+        ExtraAccountMetaList::add_to_cpi_instruction::<TestInstruction>(
+            &mut cpi_instruction,
+            &mut cpi_account_infos,
+            &buffer,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(cpi_instruction.accounts.len(), 1);
+        assert_eq!(cpi_account_infos.len(), 1);
+    }
+
     #[test]
     fn check_account_infos_test() {
         let program_id = Pubkey::new_unique();
@@ -1712,6 +2483,27 @@ mod tests {
             AccountResolutionError::IncorrectAccount.into(),
         );
 
+        // The first incorrect account is reported
+        let mismatch = ExtraAccountMetaList::find_account_mismatch::<TestInstruction>(
+            &messed_account_infos,
+            &instruction_data,
+            &program_id,
+            &buffer,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            mismatch,
+            AccountMismatch {
+                index: 0,
+                expected: AccountMeta::new(pubkey1, false),
+                provided: Some(AccountMeta::new_readonly(pubkey2, false)),
+            }
+        );
+        assert!(mismatch.is_pubkey_mismatch());
+        assert!(!mismatch.is_signer_mismatch());
+        assert!(mismatch.is_writable_mismatch());
+
         // Account info check should pass for the correct list
         assert_eq!(
             ExtraAccountMetaList::check_account_infos::<TestInstruction>(
@@ -1722,5 +2514,58 @@ mod tests {
             ),
             Ok(()),
         );
+        assert_eq!(
+            ExtraAccountMetaList::find_account_mismatch::<TestInstruction>(
+                &account_infos,
+                &instruction_data,
+                &program_id,
+                &buffer,
+            ),
+            Ok(None),
+        );
+
+        // A privilege mismatch on the right account
+        let mut readonly_account_infos = account_infos.clone().to_vec();
+        readonly_account_infos[4].is_writable = false;
+        let mismatch = ExtraAccountMetaList::find_account_mismatch::<TestInstruction>(
+            &readonly_account_infos,
+            &instruction_data,
+            &program_id,
+            &buffer,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            mismatch,
+            AccountMismatch {
+                index: 2,
+                expected: AccountMeta::new(pda, false),
+                provided: Some(AccountMeta::new_readonly(pda, false)),
+            }
+        );
+        assert!(!mismatch.is_pubkey_mismatch());
+        assert!(mismatch.is_writable_mismatch());
+
+        // The logged mismatch can be recovered from the transaction logs
+        let logs = [
+            format!("Program {} invoke [1]", program_id),
+            format!("Program log: {}", mismatch),
+            format!(
+                "Program {} failed: custom program error: 0xa261e0c0",
+                program_id
+            ),
+        ];
+        assert_eq!(AccountMismatch::from_logs(&logs), Some(mismatch));
+        let missing = AccountMismatch {
+            index: 3,
+            expected: AccountMeta::new_readonly(key_data_pubkey, true),
+            provided: None,
+        };
+        assert!(missing.is_pubkey_mismatch());
+        assert_eq!(
+            AccountMismatch::from_logs(&[format!("Program log: {}", missing)]),
+            Some(missing)
+        );
+        assert_eq!(AccountMismatch::from_logs(&logs[..1]), None);
     }
 }
@@ -0,0 +1,75 @@
+//! Types describing how extra required accounts were resolved, to debug
+//! validation data
+//!
+//! See `ExtraAccountMetaList::explain`.
+
+use {
+    crate::{account::ExtraAccountMeta, pubkey_data::PubkeyData, seeds::Seed},
+    solana_instruction::AccountMeta,
+    solana_pubkey::Pubkey,
+};
+
+/// A seed configuration, along with the bytes it resolved to
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeedTrace {
+    /// The seed configuration
+    pub seed: Seed,
+    /// The bytes pulled from the literal, instruction data, account key or
+    /// account data
+    pub bytes: Vec<u8>,
+}
+
+/// How the address of an `ExtraAccountMeta` was resolved
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressTrace {
+    /// A fixed address
+    Pubkey,
+    /// A program-derived address (PDA)
+    Pda {
+        /// The index of the program in the accounts list, for a PDA of an
+        /// external program, or `None` for a PDA of the executing program
+        program_index: Option<usize>,
+        /// The program used to derive the address
+        program_id: Pubkey,
+        /// The seeds used to derive the address, in order
+        seeds: Vec<SeedTrace>,
+    },
+    /// An address read from instruction data or account data
+    PubkeyData {
+        /// The pubkey data configuration
+        key_data: PubkeyData,
+        /// The bytes read from the instruction data or account data
+        bytes: Vec<u8>,
+    },
+}
+
+/// Trace of the resolution of one extra required account
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtraAccountMetaTrace {
+    /// The index of the account in the list of extra accounts
+    pub index: usize,
+    /// The index of the account in the entire accounts list
+    pub account_index: usize,
+    /// The configuration of the account, as stored in the validation data
+    pub extra_account_meta: ExtraAccountMeta,
+    /// How the address was resolved
+    pub address: AddressTrace,
+    /// The account meta resolved from the configuration
+    pub resolved_meta: AccountMeta,
+    /// The account meta added to the instruction, which may have been
+    /// de-escalated to not be a signer or not be writable, if it's already
+    /// in the instruction with lower privileges
+    pub meta: AccountMeta,
+}
+
+impl ExtraAccountMetaTrace {
+    /// Whether the account was de-escalated to not be a signer
+    pub fn is_signer_de_escalated(&self) -> bool {
+        self.resolved_meta.is_signer && !self.meta.is_signer
+    }
+
+    /// Whether the account was de-escalated to not be writable
+    pub fn is_writable_de_escalated(&self) -> bool {
+        self.resolved_meta.is_writable && !self.meta.is_writable
+    }
+}
@@ -0,0 +1,57 @@
+//! Types describing problems in a list of extra required accounts that
+//! prevent it from ever being resolved
+//!
+//! See `ExtraAccountMetaList::validate`.
+
+use std::fmt;
+
+/// A problem with one `ExtraAccountMeta` in a list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtraAccountMetaIssue {
+    /// The index of the account in the list of extra accounts
+    pub index: usize,
+    /// The problem found
+    pub kind: ExtraAccountMetaIssueKind,
+}
+
+/// The kinds of problems with an `ExtraAccountMeta`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtraAccountMetaIssueKind {
+    /// The discriminator is neither a fixed address, a PDA, pubkey data, nor
+    /// an external PDA
+    UnknownDiscriminator {
+        /// The discriminator found
+        discriminator: u8,
+    },
+    /// The seeds or pubkey data can't be unpacked from the address config
+    InvalidAddressConfig,
+    /// An account index points past all of the accounts of the instruction
+    AccountIndexOutOfRange {
+        /// The account index found
+        account_index: usize,
+    },
+    /// An account index points to this extra account or a later one, which
+    /// isn't resolved yet
+    ForwardReference {
+        /// The account index found
+        account_index: usize,
+    },
+}
+
+impl fmt::Display for ExtraAccountMetaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Extra account {}: ", self.index)?;
+        match &self.kind {
+            ExtraAccountMetaIssueKind::UnknownDiscriminator { discriminator } => {
+                write!(f, "unknown discriminator {}", discriminator)
+            }
+            ExtraAccountMetaIssueKind::InvalidAddressConfig => write!(f, "invalid address config"),
+            ExtraAccountMetaIssueKind::AccountIndexOutOfRange { account_index } => {
+                write!(f, "account index {} out of range", account_index)
+            }
+            ExtraAccountMetaIssueKind::ForwardReference { account_index } => {
+                write!(f, "account index {} is not resolved yet", account_index)
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Checks that the on-chain account checks don't allocate for every extra
+//! account, even when resolving PDAs, by counting the heap allocations of the
+//! current thread
+
+// allocation counts are small, and only compared in the test
+#![allow(clippy::arithmetic_side_effects)]
+
+use {
+    solana_account_info::AccountInfo,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_pubkey::Pubkey,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+    },
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    },
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Counts the heap allocations made by the given function on this thread
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let start = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - start)
+}
+
+struct TestInstruction;
+impl SplDiscriminate for TestInstruction {
+    const SPL_DISCRIMINATOR: ArrayDiscriminator =
+        ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+}
+
+struct TestAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+/// Counts the allocations of `check_account_infos` and
+/// `add_to_cpi_instruction` with the given number of extra accounts, which are
+/// PDAs derived from a literal, the data of the first account, and the key of
+/// the second account
+fn count_check_allocations(num_extra_accounts: usize) -> (usize, usize) {
+    let program_id = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let base_key = Pubkey::new_unique();
+    let base_data = vec![7; 8];
+    let extra_seeds = (0..num_extra_accounts)
+        .map(|i| {
+            [
+                Seed::Literal {
+                    bytes: vec![i as u8],
+                },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: 0,
+                    length: 8,
+                },
+                Seed::AccountKey { index: 1 },
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut accounts = vec![
+        TestAccount {
+            key: Pubkey::new_unique(),
+            lamports: 0,
+            data: base_data.clone(),
+        },
+        TestAccount {
+            key: base_key,
+            lamports: 0,
+            data: vec![],
+        },
+    ];
+    accounts.extend((0..num_extra_accounts).map(|i| {
+        TestAccount {
+            key: Pubkey::find_program_address(
+                &[&[i as u8], &base_data, base_key.as_ref()],
+                &program_id,
+            )
+            .0,
+            lamports: 0,
+            data: vec![],
+        }
+    }));
+    let account_infos = accounts
+        .iter_mut()
+        .map(|account| {
+            AccountInfo::new(
+                &account.key,
+                false,
+                false,
+                &mut account.lamports,
+                &mut account.data,
+                &owner,
+                false,
+                0,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let extra_metas = extra_seeds
+        .iter()
+        .map(|seeds| ExtraAccountMeta::new_with_seeds(seeds, false, false).unwrap())
+        .collect::<Vec<_>>();
+    let mut buffer = vec![0; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+    ExtraAccountMetaList::init::<TestInstruction>(&mut buffer, &extra_metas).unwrap();
+
+    let (result, check_allocations) = count_allocations(|| {
+        ExtraAccountMetaList::check_account_infos::<TestInstruction>(
+            &account_infos,
+            &[],
+            &program_id,
+            &buffer,
+        )
+    });
+    result.unwrap();
+
+    let mut cpi_instruction = Instruction::new_with_bytes(
+        program_id,
+        &[],
+        vec![
+            AccountMeta::new_readonly(*account_infos[0].key, false),
+            AccountMeta::new_readonly(*account_infos[1].key, false),
+        ],
+    );
+    let mut cpi_account_infos = account_infos[..2].to_vec();
+    let (result, cpi_allocations) = count_allocations(|| {
+        ExtraAccountMetaList::add_to_cpi_instruction::<TestInstruction>(
+            &mut cpi_instruction,
+            &mut cpi_account_infos,
+            &buffer,
+            &account_infos,
+        )
+    });
+    result.unwrap();
+    assert_eq!(cpi_account_infos.len(), num_extra_accounts + 2);
+
+    // Deriving an address allocates off-chain, but it's a syscall on-chain,
+    // so leave out the allocations of deriving the same addresses
+    let (_, pda_allocations) = count_allocations(|| {
+        for i in 0..num_extra_accounts {
+            Pubkey::find_program_address(&[&[i as u8], &base_data, base_key.as_ref()], &program_id);
+        }
+    });
+
+    (
+        check_allocations - pda_allocations,
+        cpi_allocations - pda_allocations,
+    )
+}
+
+#[test]
+fn allocations_independent_of_extra_account_count() {
+    assert_eq!(count_check_allocations(1), count_check_allocations(20));
+}
@@ -7,9 +7,16 @@
 pub(crate) mod test {
     use {
         borsh::{BorshDeserialize, BorshSerialize},
+        serde::{Deserialize, Serialize},
         solana_borsh::v1::{get_instance_packed_len, try_from_slice_unchecked},
         spl_discriminator::SplDiscriminate,
-        spl_type_length_value::{variable_len_pack::VariableLenPack, SplBorshVariableLenPack},
+        spl_type_length_value::{
+            header_tail::PodHeaderTail,
+            state::{TlvState, TlvStateMut},
+            variable_len_pack::VariableLenPack,
+            SplBorshVariableLenPack, SplPodHeaderTail, SplVariableLenPack,
+        },
+        std::any::TypeId,
     };
 
     #[derive(
@@ -56,4 +63,133 @@ pub(crate) mod test {
             Vehicle::unpack_from_slice(&buffer).unwrap()
         );
     }
+
+    #[derive(
+        Clone,
+        Debug,
+        Default,
+        PartialEq,
+        BorshDeserialize,
+        BorshSerialize,
+        SplDiscriminate,
+        SplVariableLenPack,
+    )]
+    #[discriminator_hash_input("vehicle::my_default_vehicle")]
+    pub struct DefaultVehicle {
+        vin: [u8; 8],
+        plate: Vec<u8>,
+    }
+
+    #[derive(
+        Clone,
+        Debug,
+        Default,
+        PartialEq,
+        Deserialize,
+        Serialize,
+        SplDiscriminate,
+        SplVariableLenPack,
+    )]
+    #[discriminator_hash_input("vehicle::my_serde_vehicle")]
+    #[variable_len_pack(codec = "bincode")]
+    pub struct SerdeVehicle {
+        vin: [u8; 8],
+        plate: String,
+    }
+
+    #[test]
+    fn test_derive_default_codec() {
+        let vehicle = DefaultVehicle {
+            vin: [1; 8],
+            plate: vec![2; 7],
+        };
+
+        assert_eq!(
+            get_instance_packed_len::<DefaultVehicle>(&vehicle).unwrap(),
+            vehicle.get_packed_len().unwrap()
+        );
+
+        let mut buffer = vec![0u8; vehicle.get_packed_len().unwrap()];
+        vehicle.pack_into_slice(&mut buffer).unwrap();
+        assert_eq!(borsh::to_vec(&vehicle).unwrap(), buffer);
+        assert_eq!(DefaultVehicle::unpack_from_slice(&buffer).unwrap(), vehicle);
+    }
+
+    #[test]
+    fn test_derive_bincode() {
+        let vehicle = SerdeVehicle {
+            vin: [1; 8],
+            plate: "ABC 123".to_string(),
+        };
+
+        assert_eq!(
+            bincode::serialized_size(&vehicle).unwrap() as usize,
+            vehicle.get_packed_len().unwrap()
+        );
+
+        let mut buffer = vec![0u8; vehicle.get_packed_len().unwrap()];
+        vehicle.pack_into_slice(&mut buffer).unwrap();
+        assert_eq!(bincode::serialize(&vehicle).unwrap(), buffer);
+        assert_eq!(SerdeVehicle::unpack_from_slice(&buffer).unwrap(), vehicle);
+
+        // too small to pack, or to unpack
+        let mut short_buffer = vec![0u8; buffer.len() - 1];
+        assert!(vehicle.pack_into_slice(&mut short_buffer).is_err());
+        assert!(SerdeVehicle::unpack_from_slice(&buffer[..buffer.len() - 1]).is_err());
+
+        // round trip through TLV data
+        let mut tlv_data = vec![0u8; 64];
+        let mut state = TlvStateMut::unpack(&mut tlv_data).unwrap();
+        state
+            .alloc_and_pack_variable_len_entry(&vehicle, false)
+            .unwrap();
+        assert_eq!(
+            state
+                .get_first_variable_len_value::<SerdeVehicle>()
+                .unwrap(),
+            vehicle
+        );
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct FleetConfig {
+        owner: [u8; 32],
+        max_vehicles: [u8; 4],
+    }
+
+    // only describes the shape of the value, so the fields are never read
+    #[allow(dead_code)]
+    #[derive(SplDiscriminate, SplPodHeaderTail)]
+    #[discriminator_hash_input("vehicle::my_fleet")]
+    pub struct Fleet {
+        pub config: FleetConfig,
+        pub vins: [[u8; 8]],
+    }
+
+    #[test]
+    fn test_derive_header_tail() {
+        assert_eq!(
+            TypeId::of::<<Fleet as PodHeaderTail>::Header>(),
+            TypeId::of::<FleetConfig>()
+        );
+        assert_eq!(
+            TypeId::of::<<Fleet as PodHeaderTail>::Item>(),
+            TypeId::of::<[u8; 8]>()
+        );
+
+        let config = FleetConfig {
+            owner: [1; 32],
+            max_vehicles: [2; 4],
+        };
+        let mut tlv_data = vec![0u8; 128];
+        let mut state = TlvStateMut::unpack(&mut tlv_data).unwrap();
+        state
+            .alloc_and_pack_header_tail_entry::<Fleet>(&config, &[[3; 8]], false)
+            .unwrap();
+        state.push_first_header_tail_item::<Fleet>(&[4; 8]).unwrap();
+        let (header, vins) = state.get_first_header_tail::<Fleet>().unwrap();
+        assert_eq!(header, &config);
+        assert_eq!(vins, [[3; 8], [4; 8]]);
+    }
 }
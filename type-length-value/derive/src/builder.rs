@@ -2,22 +2,69 @@
 use {
     proc_macro2::{Span, TokenStream},
     quote::{quote, ToTokens},
-    syn::{parse::Parse, Generics, Ident, Item, ItemEnum, ItemStruct, WhereClause},
+    syn::{
+        parse::Parse, Attribute, Generics, Ident, Item, ItemEnum, ItemStruct, LitStr, WhereClause,
+    },
 };
 
-pub struct SplBorshVariableLenPackBuilder {
+/// The serialization format used to implement `VariableLenPack`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// `borsh`, through `solana_borsh::v1`
+    #[default]
+    Borsh,
+    /// `bincode`, for any `serde`-implemented type
+    Bincode,
+}
+
+impl Codec {
+    /// Parses the codec from the `#[variable_len_pack(codec = "...")]`
+    /// attribute, defaulting to borsh if not provided
+    fn parse_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut codec = Self::default();
+        for attr in attrs
+            .iter()
+            .filter(|a| a.path().is_ident("variable_len_pack"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("codec") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    codec = match value.value().as_str() {
+                        "borsh" => Self::Borsh,
+                        "bincode" => Self::Bincode,
+                        _ => {
+                            return Err(syn::Error::new(
+                                value.span(),
+                                "Unsupported codec, expected \"borsh\" or \"bincode\"",
+                            ))
+                        }
+                    };
+                    Ok(())
+                } else {
+                    Err(meta.error("Unsupported attribute, expected `codec`"))
+                }
+            })?;
+        }
+        Ok(codec)
+    }
+}
+
+pub struct SplVariableLenPackBuilder {
     /// The struct/enum identifier
     pub ident: Ident,
     /// The item's generic arguments (if any)
     pub generics: Generics,
     /// The item's where clause for generics (if any)
     pub where_clause: Option<WhereClause>,
+    /// The serialization format to use
+    pub codec: Codec,
 }
 
-impl TryFrom<ItemEnum> for SplBorshVariableLenPackBuilder {
+impl TryFrom<ItemEnum> for SplVariableLenPackBuilder {
     type Error = syn::Error;
 
     fn try_from(item_enum: ItemEnum) -> Result<Self, Self::Error> {
+        let codec = Codec::parse_attrs(&item_enum.attrs)?;
         let ident = item_enum.ident;
         let where_clause = item_enum.generics.where_clause.clone();
         let generics = item_enum.generics;
@@ -25,14 +72,16 @@ impl TryFrom<ItemEnum> for SplBorshVariableLenPackBuilder {
             ident,
             generics,
             where_clause,
+            codec,
         })
     }
 }
 
-impl TryFrom<ItemStruct> for SplBorshVariableLenPackBuilder {
+impl TryFrom<ItemStruct> for SplVariableLenPackBuilder {
     type Error = syn::Error;
 
     fn try_from(item_struct: ItemStruct) -> Result<Self, Self::Error> {
+        let codec = Codec::parse_attrs(&item_struct.attrs)?;
         let ident = item_struct.ident;
         let where_clause = item_struct.generics.where_clause.clone();
         let generics = item_struct.generics;
@@ -40,11 +89,12 @@ impl TryFrom<ItemStruct> for SplBorshVariableLenPackBuilder {
             ident,
             generics,
             where_clause,
+            codec,
         })
     }
 }
 
-impl Parse for SplBorshVariableLenPackBuilder {
+impl Parse for SplVariableLenPackBuilder {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let item = Item::parse(input)?;
         match item {
@@ -61,19 +111,19 @@ impl Parse for SplBorshVariableLenPackBuilder {
     }
 }
 
-impl ToTokens for SplBorshVariableLenPackBuilder {
+impl ToTokens for SplVariableLenPackBuilder {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         tokens.extend::<TokenStream>(self.into());
     }
 }
 
-impl From<&SplBorshVariableLenPackBuilder> for TokenStream {
-    fn from(builder: &SplBorshVariableLenPackBuilder) -> Self {
+impl From<&SplVariableLenPackBuilder> for TokenStream {
+    fn from(builder: &SplVariableLenPackBuilder) -> Self {
         let ident = &builder.ident;
         let generics = &builder.generics;
         let where_clause = &builder.where_clause;
-        quote! {
-            impl #generics spl_type_length_value::variable_len_pack::VariableLenPack for #ident #generics #where_clause {
+        let body = match builder.codec {
+            Codec::Borsh => quote! {
                 fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), spl_type_length_value::solana_program_error::ProgramError> {
                     borsh::to_writer(&mut dst[..], self).map_err(Into::into)
                 }
@@ -85,6 +135,29 @@ impl From<&SplBorshVariableLenPackBuilder> for TokenStream {
                 fn get_packed_len(&self) -> Result<usize, spl_type_length_value::solana_program_error::ProgramError> {
                     solana_borsh::v1::get_instance_packed_len(self).map_err(Into::into)
                 }
+            },
+            Codec::Bincode => quote! {
+                fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), spl_type_length_value::solana_program_error::ProgramError> {
+                    bincode::serialize_into(&mut dst[..], self)
+                        .map_err(|_| spl_type_length_value::solana_program_error::ProgramError::AccountDataTooSmall)
+                }
+
+                fn unpack_from_slice(src: &[u8]) -> Result<Self, spl_type_length_value::solana_program_error::ProgramError> {
+                    bincode::deserialize(src)
+                        .map_err(|_| spl_type_length_value::solana_program_error::ProgramError::InvalidAccountData)
+                }
+
+                fn get_packed_len(&self) -> Result<usize, spl_type_length_value::solana_program_error::ProgramError> {
+                    bincode::serialized_size(self)
+                        .ok()
+                        .and_then(|len| usize::try_from(len).ok())
+                        .ok_or(spl_type_length_value::solana_program_error::ProgramError::InvalidAccountData)
+                }
+            },
+        };
+        quote! {
+            impl #generics spl_type_length_value::variable_len_pack::VariableLenPack for #ident #generics #where_clause {
+                #body
             }
         }
     }
@@ -0,0 +1,108 @@
+//! The token generator for the `PodHeaderTail` macro
+use {
+    proc_macro2::{Span, TokenStream},
+    quote::{quote, ToTokens},
+    syn::{parse::Parse, Fields, Generics, Ident, Item, ItemStruct, Type, WhereClause},
+};
+
+pub struct SplPodHeaderTailBuilder {
+    /// The struct identifier
+    pub ident: Ident,
+    /// The item's generic arguments (if any)
+    pub generics: Generics,
+    /// The item's where clause for generics (if any)
+    pub where_clause: Option<WhereClause>,
+    /// The type of the header field
+    pub header: Type,
+    /// The type of the items in the slice field
+    pub item: Type,
+}
+
+impl TryFrom<ItemStruct> for SplPodHeaderTailBuilder {
+    type Error = syn::Error;
+
+    fn try_from(item_struct: ItemStruct) -> Result<Self, Self::Error> {
+        let fields = match &item_struct.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect::<Vec<_>>(),
+            Fields::Unit => vec![],
+        };
+        let (header, item) = match fields.as_slice() {
+            [header, tail] => match &tail.ty {
+                Type::Slice(slice) => (header.ty.clone(), (*slice.elem).clone()),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &tail.ty,
+                        "The last field must be a slice",
+                    ))
+                }
+            },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &item_struct.ident,
+                    "Expected a header field followed by a slice field",
+                ))
+            }
+        };
+        let ident = item_struct.ident;
+        let where_clause = item_struct.generics.where_clause.clone();
+        let generics = item_struct.generics;
+        Ok(Self {
+            ident,
+            generics,
+            where_clause,
+            header,
+            item,
+        })
+    }
+}
+
+impl Parse for SplPodHeaderTailBuilder {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let item = Item::parse(input)?;
+        match item {
+            Item::Struct(item_struct) => item_struct.try_into(),
+            _ => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "Only structs are supported",
+                ))
+            }
+        }
+        .map_err(|e| syn::Error::new(input.span(), format!("Failed to parse item: {}", e)))
+    }
+}
+
+impl ToTokens for SplPodHeaderTailBuilder {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        tokens.extend::<TokenStream>(self.into());
+    }
+}
+
+impl From<&SplPodHeaderTailBuilder> for TokenStream {
+    fn from(builder: &SplPodHeaderTailBuilder) -> Self {
+        let ident = &builder.ident;
+        let generics = &builder.generics;
+        let where_clause = &builder.where_clause;
+        let header = &builder.header;
+        let item = &builder.item;
+        // The items start right after the header, so a header whose size
+        // isn't a multiple of the item alignment is rejected where the
+        // struct is defined. Generic structs are checked when used instead.
+        let alignment_check = generics.params.is_empty().then(|| {
+            quote! {
+                const _: () = assert!(
+                    ::core::mem::size_of::<#header>() % ::core::mem::align_of::<#item>() == 0,
+                    "the header size must be a multiple of the item alignment"
+                );
+            }
+        });
+        quote! {
+            impl #generics spl_type_length_value::header_tail::PodHeaderTail for #ident #generics #where_clause {
+                type Header = #header;
+                type Item = #item;
+            }
+            #alignment_check
+        }
+    }
+}
@@ -1,5 +1,5 @@
-//! Crate defining a derive macro for a basic borsh implementation of
-//! the trait `VariableLenPack`.
+//! Crate defining derive macros for basic borsh and serde implementations of
+//! the trait `VariableLenPack`, and for the zero-copy trait `PodHeaderTail`.
 
 #![deny(missing_docs)]
 #![cfg_attr(not(test), forbid(unsafe_code))]
@@ -7,16 +7,46 @@
 extern crate proc_macro;
 
 mod builder;
+mod header_tail;
 
 use {
-    builder::SplBorshVariableLenPackBuilder, proc_macro::TokenStream, quote::ToTokens,
+    builder::{Codec, SplVariableLenPackBuilder},
+    header_tail::SplPodHeaderTailBuilder,
+    proc_macro::TokenStream,
+    quote::ToTokens,
     syn::parse_macro_input,
 };
 
 /// Derive macro to add `VariableLenPack` trait for borsh-implemented types
 #[proc_macro_derive(SplBorshVariableLenPack)]
 pub fn spl_borsh_variable_len_pack(input: TokenStream) -> TokenStream {
-    parse_macro_input!(input as SplBorshVariableLenPackBuilder)
+    let mut builder = parse_macro_input!(input as SplVariableLenPackBuilder);
+    builder.codec = Codec::Borsh;
+    builder.to_token_stream().into()
+}
+
+/// Derive macro to add `VariableLenPack` trait, with the codec selected by
+/// the `#[variable_len_pack(codec = "...")]` attribute.
+///
+/// Supported codecs are `"borsh"`, the default, and `"bincode"` for
+/// serde-implemented types. The generated code uses the `borsh` and
+/// `solana_borsh` crates, or the `bincode` crate, from the deriving crate.
+#[proc_macro_derive(SplVariableLenPack, attributes(variable_len_pack))]
+pub fn spl_variable_len_pack(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as SplVariableLenPackBuilder)
+        .to_token_stream()
+        .into()
+}
+
+/// Derive macro to add `PodHeaderTail` trait for a struct made of a `Pod`
+/// header field followed by a slice of `Pod` items, such as
+/// `struct MyList { config: MyConfig, keys: [Pubkey] }`
+///
+/// The size of the header must be a multiple of the alignment of the items,
+/// otherwise the derive fails to compile.
+#[proc_macro_derive(SplPodHeaderTail)]
+pub fn spl_pod_header_tail(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as SplPodHeaderTailBuilder)
         .to_token_stream()
         .into()
 }
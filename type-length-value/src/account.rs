@@ -0,0 +1,117 @@
+//! The [`TlvAccount`] trait, to work with account data from any runtime.
+
+use {
+    solana_account_info::AccountInfo,
+    solana_program_error::ProgramError,
+    std::{
+        cell::{Ref, RefCell, RefMut},
+        ops::{Deref, DerefMut},
+    },
+};
+
+/// Trait for an account holding TLV data, covering what's needed by the
+/// reallocating helpers in `state`.
+///
+/// Implemented for `AccountInfo` and `InMemoryAccount`, and can be implemented
+/// for the account types of other runtimes.
+pub trait TlvAccount {
+    /// Guard returned when borrowing the account data
+    type Data<'a>: Deref<Target = [u8]>
+    where
+        Self: 'a;
+    /// Guard returned when mutably borrowing the account data
+    type DataMut<'a>: DerefMut<Target = [u8]>
+    where
+        Self: 'a;
+
+    /// Borrows the account data
+    fn try_borrow_data(&self) -> Result<Self::Data<'_>, ProgramError>;
+
+    /// Mutably borrows the account data
+    fn try_borrow_mut_data(&self) -> Result<Self::DataMut<'_>, ProgramError>;
+
+    /// Gets the length of the account data
+    fn try_data_len(&self) -> Result<usize, ProgramError>;
+
+    /// Resizes the account data, zeroing any new bytes if `zero_init` is set
+    fn realloc(&self, new_len: usize, zero_init: bool) -> Result<(), ProgramError>;
+}
+
+impl TlvAccount for AccountInfo<'_> {
+    type Data<'a>
+        = Ref<'a, [u8]>
+    where
+        Self: 'a;
+    type DataMut<'a>
+        = RefMut<'a, [u8]>
+    where
+        Self: 'a;
+
+    fn try_borrow_data(&self) -> Result<Self::Data<'_>, ProgramError> {
+        AccountInfo::try_borrow_data(self).map(|data| Ref::map(data, |data| &**data))
+    }
+
+    fn try_borrow_mut_data(&self) -> Result<Self::DataMut<'_>, ProgramError> {
+        AccountInfo::try_borrow_mut_data(self).map(|data| RefMut::map(data, |data| &mut **data))
+    }
+
+    fn try_data_len(&self) -> Result<usize, ProgramError> {
+        AccountInfo::try_data_len(self)
+    }
+
+    fn realloc(&self, new_len: usize, zero_init: bool) -> Result<(), ProgramError> {
+        AccountInfo::realloc(self, new_len, zero_init)
+    }
+}
+
+/// Account data held in memory, for tests and off-chain tools
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InMemoryAccount {
+    data: RefCell<Vec<u8>>,
+}
+
+impl InMemoryAccount {
+    /// Creates an account holding the given data
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data: RefCell::new(data),
+        }
+    }
+
+    /// Returns the account data
+    pub fn into_data(self) -> Vec<u8> {
+        self.data.into_inner()
+    }
+}
+
+impl TlvAccount for InMemoryAccount {
+    type Data<'a> = Ref<'a, [u8]>;
+    type DataMut<'a> = RefMut<'a, [u8]>;
+
+    fn try_borrow_data(&self) -> Result<Self::Data<'_>, ProgramError> {
+        self.data
+            .try_borrow()
+            .map(|data| Ref::map(data, Vec::as_slice))
+            .map_err(|_| ProgramError::AccountBorrowFailed)
+    }
+
+    fn try_borrow_mut_data(&self) -> Result<Self::DataMut<'_>, ProgramError> {
+        self.data
+            .try_borrow_mut()
+            .map(|data| RefMut::map(data, Vec::as_mut_slice))
+            .map_err(|_| ProgramError::AccountBorrowFailed)
+    }
+
+    fn try_data_len(&self) -> Result<usize, ProgramError> {
+        self.try_borrow_data().map(|data| data.len())
+    }
+
+    fn realloc(&self, new_len: usize, _zero_init: bool) -> Result<(), ProgramError> {
+        // new bytes are always zeroed out
+        self.data
+            .try_borrow_mut()
+            .map_err(|_| ProgramError::AccountBorrowFailed)?
+            .resize(new_len, 0);
+        Ok(())
+    }
+}
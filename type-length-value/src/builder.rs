@@ -0,0 +1,389 @@
+//! Off-chain builder for TLV data that grows as entries are written
+
+use {
+    crate::{
+        layout::TlvLayout,
+        state::{get_discriminators_and_end_index, get_value_range, TlvState, TlvStateMut},
+        variable_len_pack::VariableLenPack,
+    },
+    bytemuck::Pod,
+    solana_program_error::ProgramError,
+    spl_discriminator::SplDiscriminate,
+    spl_pod::bytemuck::pod_from_bytes_mut,
+    std::{cmp::Ordering, mem::size_of},
+};
+
+/// Builder for TLV data, backed by a `Vec<u8>` that grows and shrinks as
+/// entries are allocated and reallocated.
+///
+/// Meant for clients that need to create the data for a new account without
+/// computing its size ahead of time. The buffer never contains any
+/// uninitialized space, so once all entries are written, `finish` gives back
+/// bytes of the exact size required for the account.
+///
+/// ```
+/// use {
+///     bytemuck::{Pod, Zeroable},
+///     spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+///     spl_type_length_value::{builder::TlvBuilder, state::TlvState},
+/// };
+/// #[repr(C)]
+/// #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+/// struct MyPodValue {
+///     data: [u8; 8],
+/// }
+/// impl SplDiscriminate for MyPodValue {
+///     const SPL_DISCRIMINATOR: ArrayDiscriminator = ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+/// }
+/// let mut builder = TlvBuilder::new();
+/// let (value, _) = builder.init_value::<MyPodValue>(false).unwrap();
+/// value.data = [2; 8];
+/// let (data, account_len) = builder.finish();
+/// assert_eq!(account_len, TlvBuilder::get_base_len() + 8);
+/// assert_eq!(data.len(), account_len);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlvBuilder {
+    /// Initialized TLV entries, without any trailing space
+    data: Vec<u8>,
+    /// Layout of the type and length of every entry
+    layout: TlvLayout,
+}
+impl TlvBuilder {
+    /// Creates an empty builder using the default layout
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty builder using the given layout
+    pub fn new_with_layout(layout: TlvLayout) -> Self {
+        Self {
+            data: vec![],
+            layout,
+        }
+    }
+
+    /// Creates a builder from existing TLV data, such as the data of an
+    /// account to be updated. Any trailing uninitialized space is dropped.
+    pub fn unpack(data: Vec<u8>) -> Result<Self, ProgramError> {
+        Self::unpack_with_layout(data, TlvLayout::DEFAULT)
+    }
+
+    /// Creates a builder from existing TLV data using the given layout. Any
+    /// trailing uninitialized space is dropped.
+    pub fn unpack_with_layout(mut data: Vec<u8>, layout: TlvLayout) -> Result<Self, ProgramError> {
+        let (_, end_index) = get_discriminators_and_end_index(&layout, &data)?;
+        data.truncate(end_index);
+        Ok(Self { data, layout })
+    }
+
+    /// Get a mutable view of the data, to update entries in place
+    pub fn as_state_mut(&mut self) -> Result<TlvStateMut<'_>, ProgramError> {
+        TlvStateMut::unpack_with_layout(&mut self.data, self.layout)
+    }
+
+    /// Grows the buffer and allocates the given number of bytes for the given
+    /// `SplDiscriminate` at the end. See `TlvStateMut::alloc` for more
+    /// information.
+    pub fn alloc<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        allow_repetition: bool,
+    ) -> Result<(&mut [u8], usize), ProgramError> {
+        let previous_len = self.data.len();
+        self.data.resize(
+            previous_len.saturating_add(self.layout.entry_len(length)),
+            0,
+        );
+        let result = TlvStateMut::unpack_with_layout(&mut self.data, self.layout)
+            .and_then(|mut state| state.alloc::<V>(length, allow_repetition).map(|(_, n)| n));
+        match result {
+            Ok(repetition_number) => {
+                let value_start = previous_len.saturating_add(self.layout.base_len());
+                let value_end = value_start.saturating_add(length);
+                Ok((&mut self.data[value_start..value_end], repetition_number))
+            }
+            Err(e) => {
+                self.data.truncate(previous_len);
+                Err(e)
+            }
+        }
+    }
+
+    /// Grows the buffer and packs the default TLV data at the end. See
+    /// `TlvStateMut::init_value` for more information.
+    pub fn init_value<V: SplDiscriminate + Pod + Default>(
+        &mut self,
+        allow_repetition: bool,
+    ) -> Result<(&mut V, usize), ProgramError> {
+        let (buffer, repetition_number) = self.alloc::<V>(size_of::<V>(), allow_repetition)?;
+        let extension_ref = pod_from_bytes_mut::<V>(buffer)?;
+        *extension_ref = V::default();
+        Ok((extension_ref, repetition_number))
+    }
+
+    /// Grows the buffer and serializes a new TLV entry from a `VariableLenPack`
+    /// type at the end
+    pub fn alloc_and_pack_variable_len_entry<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+        allow_repetition: bool,
+    ) -> Result<usize, ProgramError> {
+        let length = value.get_packed_len()?;
+        let (data, repetition_number) = self.alloc::<V>(length, allow_repetition)?;
+        value.pack_into_slice(data)?;
+        Ok(repetition_number)
+    }
+
+    /// Reallocate the given number of bytes for the given `SplDiscriminate`,
+    /// resizing the buffer by the difference. See
+    /// `TlvStateMut::realloc_with_repetition` for more information.
+    pub fn realloc_with_repetition<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        repetition_number: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        let old_length = get_value_range(
+            &self.layout,
+            &self.data,
+            V::SPL_DISCRIMINATOR,
+            repetition_number,
+        )?
+        .len();
+        // the buffer only changes size if the padded length changes
+        let old_padded_length = self.layout.padded_len(old_length);
+        let new_padded_length = self.layout.padded_len(length);
+        let previous_len = self.data.len();
+        match old_padded_length.cmp(&new_padded_length) {
+            Ordering::Less => {
+                self.data.resize(
+                    previous_len.saturating_add(new_padded_length - old_padded_length),
+                    0,
+                );
+                self.as_state_mut()?
+                    .realloc_with_repetition::<V>(length, repetition_number)?;
+            }
+            Ordering::Greater => {
+                self.as_state_mut()?
+                    .realloc_with_repetition::<V>(length, repetition_number)?;
+                self.data
+                    .truncate(previous_len.saturating_sub(old_padded_length - new_padded_length));
+            }
+            Ordering::Equal => {
+                self.as_state_mut()?
+                    .realloc_with_repetition::<V>(length, repetition_number)?;
+            }
+        }
+        let range = get_value_range(
+            &self.layout,
+            &self.data,
+            V::SPL_DISCRIMINATOR,
+            repetition_number,
+        )?;
+        Ok(&mut self.data[range])
+    }
+
+    /// Reallocate the given number of bytes for the given `SplDiscriminate`,
+    /// where no repeating discriminators are allowed
+    pub fn realloc_first<V: SplDiscriminate>(
+        &mut self,
+        length: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        self.realloc_with_repetition::<V>(length, 0)
+    }
+
+    /// Packs a variable-length value into an existing TLV entry, resizing the
+    /// buffer as needed to accommodate for any change in space
+    pub fn realloc_and_pack_variable_len_with_repetition<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+        repetition_number: usize,
+    ) -> Result<(), ProgramError> {
+        let length = value.get_packed_len()?;
+        let data = self.realloc_with_repetition::<V>(length, repetition_number)?;
+        value.pack_into_slice(data)
+    }
+
+    /// Packs a variable-length value into an existing TLV entry, where no
+    /// repeating discriminators are allowed
+    pub fn realloc_and_pack_first_variable_len<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+    ) -> Result<(), ProgramError> {
+        self.realloc_and_pack_variable_len_with_repetition::<V>(value, 0)
+    }
+
+    /// Consumes the builder, returning the exact-size TLV data along with its
+    /// length, which is the size to allocate for the account, and to pass
+    /// to `Rent::minimum_balance`
+    pub fn finish(self) -> (Vec<u8>, usize) {
+        let len = self.data.len();
+        (self.data, len)
+    }
+}
+impl TlvState for TlvBuilder {
+    fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn get_layout(&self) -> TlvLayout {
+        self.layout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            error::TlvError,
+            length::LengthSize,
+            state::{TlvStateBorrowed, TlvStateOwned},
+        },
+        bytemuck::Zeroable,
+        spl_discriminator::ArrayDiscriminator,
+    };
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValue {
+        data: [u8; 32],
+    }
+    impl SplDiscriminate for TestValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestVariableLen {
+        data: Vec<u8>,
+    }
+    impl SplDiscriminate for TestVariableLen {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2, 0, 0, 0, 0, 0, 0, 0]);
+    }
+    impl VariableLenPack for TestVariableLen {
+        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+            dst.get_mut(..self.data.len())
+                .ok_or(ProgramError::InvalidAccountData)?
+                .copy_from_slice(&self.data);
+            Ok(())
+        }
+        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+            Ok(Self { data: src.to_vec() })
+        }
+        fn get_packed_len(&self) -> Result<usize, ProgramError> {
+            Ok(self.data.len())
+        }
+    }
+
+    #[test]
+    fn build_exact_size() {
+        let mut builder = TlvBuilder::new();
+        builder.init_value::<TestValue>(false).unwrap().0.data = [1; 32];
+        let short = TestVariableLen { data: vec![2; 5] };
+        let long = TestVariableLen { data: vec![3; 50] };
+        assert_eq!(
+            builder
+                .alloc_and_pack_variable_len_entry(&short, true)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            builder
+                .alloc_and_pack_variable_len_entry(&short, true)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            builder.init_value::<TestValue>(false).unwrap_err(),
+            TlvError::TypeAlreadyExists.into()
+        );
+
+        // grow and shrink entries in the middle
+        builder.realloc_and_pack_first_variable_len(&long).unwrap();
+        assert_eq!(
+            builder
+                .get_first_variable_len_value::<TestVariableLen>()
+                .unwrap(),
+            long
+        );
+        builder
+            .realloc_and_pack_variable_len_with_repetition(&TestVariableLen { data: vec![] }, 1)
+            .unwrap();
+        assert_eq!(
+            builder
+                .get_variable_len_value_with_repetition::<TestVariableLen>(1)
+                .unwrap(),
+            TestVariableLen { data: vec![] }
+        );
+        assert_eq!(
+            builder.get_first_value::<TestValue>().unwrap().data,
+            [1; 32]
+        );
+
+        // update in place
+        builder
+            .as_state_mut()
+            .unwrap()
+            .get_first_value_mut::<TestValue>()
+            .unwrap()
+            .data = [4; 32];
+
+        let (data, account_len) = builder.finish();
+        assert_eq!(account_len, TlvBuilder::get_base_len() * 3 + 32 + 50);
+        assert_eq!(data.len(), account_len);
+        let state = TlvStateOwned::unpack(data.clone()).unwrap();
+        assert_eq!(state.get_first_value::<TestValue>().unwrap().data, [4; 32]);
+
+        // resume building from existing data with trailing space
+        let mut padded = data.clone();
+        padded.extend_from_slice(&[0; 20]);
+        let mut builder = TlvBuilder::unpack(padded).unwrap();
+        assert_eq!(builder.get_data(), &data[..]);
+        builder
+            .alloc_and_pack_variable_len_entry(&long, true)
+            .unwrap();
+        assert_eq!(
+            builder.get_data().len(),
+            account_len + TlvBuilder::get_base_len() + 50
+        );
+    }
+
+    #[test]
+    fn build_with_layout() {
+        let layout = TlvLayout::new(2, LengthSize::U16);
+        let mut builder = TlvBuilder::new_with_layout(layout);
+        builder
+            .alloc_and_pack_variable_len_entry(&TestVariableLen { data: vec![7; 3] }, false)
+            .unwrap();
+        // discriminator too large for the layout, nothing is written
+        assert_eq!(
+            builder.init_value::<TestValue>(false).unwrap_err(),
+            TlvError::DiscriminatorTooLarge.into()
+        );
+        let (data, account_len) = builder.finish();
+        assert_eq!(data, [2, 0, 3, 0, 7, 7, 7]);
+        assert_eq!(account_len, 7);
+        let state = TlvStateBorrowed::unpack_with_layout(&data, layout).unwrap();
+        assert_eq!(state.get_first_bytes::<TestVariableLen>().unwrap(), [7; 3]);
+
+        // padded entries grow the buffer by their padded size
+        let layout = TlvLayout::DEFAULT.with_value_alignment(8);
+        let mut builder = TlvBuilder::new_with_layout(layout);
+        builder
+            .alloc_and_pack_variable_len_entry(&TestVariableLen { data: vec![7; 3] }, false)
+            .unwrap();
+        assert_eq!(builder.get_data().len(), 24);
+        builder
+            .realloc_and_pack_first_variable_len(&TestVariableLen { data: vec![8; 9] })
+            .unwrap();
+        assert_eq!(builder.get_data().len(), 32);
+        builder
+            .realloc_and_pack_first_variable_len(&TestVariableLen { data: vec![9; 8] })
+            .unwrap();
+        let (data, account_len) = builder.finish();
+        assert_eq!(account_len, 24);
+        assert_eq!(&data[16..], [9; 8]);
+    }
+}
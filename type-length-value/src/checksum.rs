@@ -0,0 +1,48 @@
+//! The [`TlvChecksum`] integrity entry.
+
+use {
+    bytemuck::{Pod, Zeroable},
+    solana_sha256_hasher::hashv,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+};
+
+/// Reserved TLV entry holding a truncated SHA-256 hash of all of the entries
+/// before it, used to detect corrupted data.
+///
+/// It's kept up to date at the end of the data by `TlvStateMut::with_checksum`,
+/// and checked by `TlvStateBorrowed::unpack_verified`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct TlvChecksum {
+    /// The first bytes of the SHA-256 hash of the preceding entries
+    pub hash: [u8; 8],
+}
+
+impl TlvChecksum {
+    /// Computes the checksum of the given entries
+    pub fn compute(entries: &[u8]) -> Self {
+        let mut hash = [0; 8];
+        hash.copy_from_slice(&hashv(&[entries]).to_bytes()[..8]);
+        Self { hash }
+    }
+}
+
+impl SplDiscriminate for TlvChecksum {
+    /// First 8 bytes of the SHA-256 hash of
+    /// `"spl_type_length_value:checksum"`
+    const SPL_DISCRIMINATOR: ArrayDiscriminator =
+        ArrayDiscriminator::new([46, 167, 170, 109, 24, 226, 148, 133]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn discriminator() {
+        assert_eq!(
+            TlvChecksum::SPL_DISCRIMINATOR,
+            ArrayDiscriminator::new_with_hash_input("spl_type_length_value:checksum")
+        );
+    }
+}
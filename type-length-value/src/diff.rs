@@ -0,0 +1,176 @@
+//! Structural comparison of two TLV states
+
+use {
+    crate::state::TlvState,
+    solana_program_error::ProgramError,
+    spl_discriminator::ArrayDiscriminator,
+    std::{collections::HashMap, ops::Range},
+};
+
+/// A TLV entry that differs between two states, keyed by its discriminator and
+/// repetition number
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlvDiffEntry {
+    /// The discriminator of the entry
+    pub discriminator: ArrayDiscriminator,
+    /// The repetition number of the entry
+    pub repetition_number: usize,
+    /// The range of the value bytes in the old data, if present
+    pub old_range: Option<Range<usize>>,
+    /// The range of the value bytes in the new data, if present
+    pub new_range: Option<Range<usize>>,
+}
+
+/// Entries that were added, removed or modified between two TLV states
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlvDiff {
+    /// Entries only present in the new data, in the new data's order
+    pub added: Vec<TlvDiffEntry>,
+    /// Entries only present in the old data, in the old data's order
+    pub removed: Vec<TlvDiffEntry>,
+    /// Entries present in both, but with different value bytes, in the old
+    /// data's order
+    pub modified: Vec<TlvDiffEntry>,
+}
+
+impl TlvDiff {
+    /// Checks if both states have the same entries, regardless of position
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Compares the entries of two TLV states, such as the data of an account
+/// before and after a transaction.
+///
+/// Entries are matched by discriminator and repetition number, so an entry
+/// that only moved within the buffer is not reported.
+pub fn tlv_diff(old: &impl TlvState, new: &impl TlvState) -> Result<TlvDiff, ProgramError> {
+    let mut new_entries = HashMap::new();
+    let mut new_order = vec![];
+    for entry in new.iter() {
+        let (discriminator, repetition_number, value_start, value) = entry?;
+        let range = value_start..value_start.saturating_add(value.len());
+        let key = (
+            <[u8; ArrayDiscriminator::LENGTH]>::from(discriminator),
+            repetition_number,
+        );
+        new_entries.insert(key, (range, value));
+        new_order.push((discriminator, repetition_number));
+    }
+
+    let mut diff = TlvDiff::default();
+    for entry in old.iter() {
+        let (discriminator, repetition_number, value_start, old_value) = entry?;
+        let old_range = value_start..value_start.saturating_add(old_value.len());
+        let key = (
+            <[u8; ArrayDiscriminator::LENGTH]>::from(discriminator),
+            repetition_number,
+        );
+        match new_entries.remove(&key) {
+            Some((new_range, new_value)) => {
+                if old_value != new_value {
+                    diff.modified.push(TlvDiffEntry {
+                        discriminator,
+                        repetition_number,
+                        old_range: Some(old_range),
+                        new_range: Some(new_range),
+                    });
+                }
+            }
+            None => diff.removed.push(TlvDiffEntry {
+                discriminator,
+                repetition_number,
+                old_range: Some(old_range),
+                new_range: None,
+            }),
+        }
+    }
+    for (discriminator, repetition_number) in new_order {
+        let key = (
+            <[u8; ArrayDiscriminator::LENGTH]>::from(discriminator),
+            repetition_number,
+        );
+        if let Some((new_range, _)) = new_entries.remove(&key) {
+            diff.added.push(TlvDiffEntry {
+                discriminator,
+                repetition_number,
+                old_range: None,
+                new_range: Some(new_range),
+            });
+        }
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::{TlvStateBorrowed, TlvStateMut},
+        spl_discriminator::SplDiscriminate,
+    };
+
+    struct TestValue;
+    impl SplDiscriminate for TestValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+    }
+
+    struct TestOtherValue;
+    impl SplDiscriminate for TestOtherValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[test]
+    fn diff_entries() {
+        let mut old_buffer = vec![0; 128];
+        let mut state = TlvStateMut::unpack(&mut old_buffer).unwrap();
+        state.alloc::<TestValue>(2, true).unwrap().0.fill(1);
+        state.alloc::<TestOtherValue>(3, false).unwrap().0.fill(2);
+        state.alloc::<TestValue>(1, true).unwrap().0.fill(3);
+
+        // same entries, same bytes
+        let mut new_buffer = old_buffer.clone();
+        let old = TlvStateBorrowed::unpack(&old_buffer).unwrap();
+        let diff = tlv_diff(&old, &TlvStateBorrowed::unpack(&new_buffer).unwrap()).unwrap();
+        assert!(diff.is_empty());
+
+        // remove the other value, which moves the second entry without
+        // changing it, modify the first entry, and add a third entry
+        let mut state = TlvStateMut::unpack(&mut new_buffer).unwrap();
+        state.remove_first::<TestOtherValue>().unwrap();
+        state
+            .get_bytes_with_repetition_mut::<TestValue>(0)
+            .unwrap()
+            .fill(4);
+        state.alloc::<TestValue>(5, true).unwrap().0.fill(5);
+
+        let base_len = TlvStateBorrowed::get_base_len();
+        let diff = tlv_diff(&old, &state).unwrap();
+        assert_eq!(
+            diff,
+            TlvDiff {
+                added: vec![TlvDiffEntry {
+                    discriminator: TestValue::SPL_DISCRIMINATOR,
+                    repetition_number: 2,
+                    old_range: None,
+                    new_range: Some(base_len * 3 + 3..base_len * 3 + 8),
+                }],
+                removed: vec![TlvDiffEntry {
+                    discriminator: TestOtherValue::SPL_DISCRIMINATOR,
+                    repetition_number: 0,
+                    old_range: Some(base_len * 2 + 2..base_len * 2 + 5),
+                    new_range: None,
+                }],
+                modified: vec![TlvDiffEntry {
+                    discriminator: TestValue::SPL_DISCRIMINATOR,
+                    repetition_number: 0,
+                    old_range: Some(base_len..base_len + 2),
+                    new_range: Some(base_len..base_len + 2),
+                }],
+            }
+        );
+    }
+}
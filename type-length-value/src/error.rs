@@ -14,6 +14,30 @@ pub enum TlvError {
     /// Type already exists in TLV data
     #[error("Type already exists in TLV data")]
     TypeAlreadyExists,
+    /// Discriminator does not fit in the TLV layout
+    #[error("Discriminator does not fit in the TLV layout")]
+    DiscriminatorTooLarge,
+    /// Not enough bytes for the type and length of an entry
+    #[error("Not enough bytes for the type and length of an entry")]
+    TruncatedHeader,
+    /// Length of an entry runs past the end of the TLV data
+    #[error("Length of an entry runs past the end of the TLV data")]
+    LengthOverflow,
+    /// Non-zero data found after the end of the TLV entries
+    #[error("Non-zero data found after the end of the TLV entries")]
+    TrailingData,
+    /// Same TLV entry requested more than once
+    #[error("Same TLV entry requested more than once")]
+    DuplicateEntry,
+    /// TLV checksum does not match the data
+    #[error("TLV checksum does not match the data")]
+    ChecksumMismatch,
+    /// Migrated type shares its discriminator with its previous version, but
+    /// has no version byte
+    #[error(
+        "Migrated type shares its discriminator with its previous version, but has no version byte"
+    )]
+    MissingVersion,
 }
 
 impl From<TlvError> for ProgramError {
@@ -34,6 +58,85 @@ impl PrintProgramError for TlvError {
             TlvError::TypeAlreadyExists => {
                 msg!("Type already exists in TLV data")
             }
+            TlvError::DiscriminatorTooLarge => {
+                msg!("Discriminator does not fit in the TLV layout")
+            }
+            TlvError::TruncatedHeader => {
+                msg!("Not enough bytes for the type and length of an entry")
+            }
+            TlvError::LengthOverflow => {
+                msg!("Length of an entry runs past the end of the TLV data")
+            }
+            TlvError::TrailingData => {
+                msg!("Non-zero data found after the end of the TLV entries")
+            }
+            TlvError::DuplicateEntry => {
+                msg!("Same TLV entry requested more than once")
+            }
+            TlvError::ChecksumMismatch => {
+                msg!("TLV checksum does not match the data")
+            }
+            TlvError::MissingVersion => {
+                msg!("Migrated type shares its discriminator with its previous version, but has no version byte")
+            }
+        }
+    }
+}
+
+/// Structural problem found in TLV data, along with where it was found
+#[derive(Clone, Debug, Eq, thiserror::Error, PartialEq)]
+pub enum TlvDataError {
+    /// Not enough bytes for the type and length of the entry starting at
+    /// `offset`
+    #[error("Not enough bytes for the type and length of the entry at offset {offset}")]
+    TruncatedHeader {
+        /// Start of the entry
+        offset: usize,
+    },
+    /// Length of the entry starting at `offset` runs past the end of the data
+    #[error("Length {length} of the entry at offset {offset} runs past the end of the TLV data")]
+    LengthOverflow {
+        /// Start of the entry
+        offset: usize,
+        /// Length written in the entry
+        length: usize,
+    },
+    /// Non-zero data found after the end of the TLV entries, starting at
+    /// `offset`
+    #[error("Non-zero data found at offset {offset}, after the end of the TLV entries")]
+    TrailingData {
+        /// Position of the first non-zero byte
+        offset: usize,
+    },
+}
+
+impl From<TlvDataError> for TlvError {
+    fn from(e: TlvDataError) -> Self {
+        match e {
+            TlvDataError::TruncatedHeader { .. } => TlvError::TruncatedHeader,
+            TlvDataError::LengthOverflow { .. } => TlvError::LengthOverflow,
+            TlvDataError::TrailingData { .. } => TlvError::TrailingData,
         }
     }
 }
+
+impl From<TlvDataError> for ProgramError {
+    fn from(e: TlvDataError) -> Self {
+        TlvError::from(e).into()
+    }
+}
+
+/// Errors that may be returned when streaming TLV data with `TlvReader` and
+/// `TlvWriter`
+#[derive(Debug, thiserror::Error)]
+pub enum TlvStreamError {
+    /// Error from the underlying reader or writer
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Malformed TLV data, with offsets from the start of the stream
+    #[error(transparent)]
+    Data(#[from] TlvDataError),
+    /// Error while writing an entry
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+}
@@ -0,0 +1,78 @@
+//! The [`PodHeaderTail`] zero-copy variable-length trait.
+
+use {
+    bytemuck::Pod,
+    solana_program_error::ProgramError,
+    spl_pod::bytemuck::{
+        pod_from_bytes, pod_from_bytes_mut, pod_slice_from_bytes, pod_slice_from_bytes_mut,
+    },
+    std::mem::{align_of, size_of},
+};
+
+/// Trait for variable-length values made of a fixed `Pod` header followed by
+/// any number of `Pod` items, such as a config followed by a list of pubkeys.
+///
+/// Unlike `VariableLenPack`, these values are never deserialized into an
+/// owned type, and are read and written directly in the TLV data.
+///
+/// The items start right after the header, so the size of the header must be
+/// a multiple of the alignment of the items. Otherwise the items could never
+/// be read in place, so using such a type fails to compile.
+///
+/// With the `derive` feature, the `SplPodHeaderTail` derive macro implements
+/// this trait for a struct with a header field followed by a slice field:
+///
+/// ```rust,ignore
+/// #[derive(SplDiscriminate, SplPodHeaderTail)]
+/// #[discriminator_hash_input("my_config_list")]
+/// struct MyConfigList {
+///     config: MyConfig,
+///     keys: [Pubkey],
+/// }
+/// ```
+pub trait PodHeaderTail {
+    /// The fixed-size header at the start of the value
+    type Header: Pod;
+    /// The type of every item after the header
+    type Item: Pod;
+}
+
+/// Fails the build if the items after the header of `V` would be misaligned
+const fn assert_tail_aligned<V: PodHeaderTail + ?Sized>() {
+    const {
+        assert!(
+            size_of::<V::Header>() % align_of::<V::Item>() == 0,
+            "the header size must be a multiple of the item alignment"
+        )
+    }
+}
+
+/// Gets the packed length of a value with the given number of items
+pub fn get_header_tail_len<V: PodHeaderTail + ?Sized>(num_items: usize) -> usize {
+    assert_tail_aligned::<V>();
+    size_of::<V::Header>().saturating_add(size_of::<V::Item>().saturating_mul(num_items))
+}
+
+/// Unpacks the header and items from the given bytes
+pub fn header_tail_from_bytes<V: PodHeaderTail + ?Sized>(
+    bytes: &[u8],
+) -> Result<(&V::Header, &[V::Item]), ProgramError> {
+    assert_tail_aligned::<V>();
+    if bytes.len() < size_of::<V::Header>() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let (header, tail) = bytes.split_at(size_of::<V::Header>());
+    Ok((pod_from_bytes(header)?, pod_slice_from_bytes(tail)?))
+}
+
+/// Unpacks the header and items from the given bytes, mutably
+pub fn header_tail_from_bytes_mut<V: PodHeaderTail + ?Sized>(
+    bytes: &mut [u8],
+) -> Result<(&mut V::Header, &mut [V::Item]), ProgramError> {
+    assert_tail_aligned::<V>();
+    if bytes.len() < size_of::<V::Header>() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let (header, tail) = bytes.split_at_mut(size_of::<V::Header>());
+    Ok((pod_from_bytes_mut(header)?, pod_slice_from_bytes_mut(tail)?))
+}
@@ -0,0 +1,420 @@
+//! TLV state wrapper with a cached offset index, for repeated lookups
+
+use {
+    crate::{
+        error::TlvError,
+        layout::TlvLayout,
+        state::{get_all_entry_bounds, TlvState, TlvStateMut},
+        variable_len_pack::VariableLenPack,
+    },
+    bytemuck::Pod,
+    solana_program_error::ProgramError,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_pod::bytemuck::{pod_from_bytes, pod_from_bytes_mut},
+    std::{collections::HashMap, ops::Range},
+};
+
+/// Wrapper around any TLV state that walks the data once, storing the value
+/// ranges of every entry by discriminator and repetition number.
+///
+/// Every lookup after that is a single map access, rather than reading every
+/// header from the start of the data. This is useful for programs that read
+/// many entries from the same account.
+///
+/// When wrapping a `TlvStateMut`, any operation that changes the size or
+/// position of entries rebuilds the index, so it can never point to stale
+/// offsets.
+#[derive(Debug, PartialEq)]
+pub struct IndexedTlvState<S> {
+    state: S,
+    index: HashMap<[u8; ArrayDiscriminator::LENGTH], Vec<Range<usize>>>,
+}
+impl<S: TlvState> IndexedTlvState<S> {
+    /// Wraps the TLV state, walking through all of its entries once
+    pub fn new(state: S) -> Result<Self, ProgramError> {
+        let index = build_index(&state)?;
+        Ok(Self { state, index })
+    }
+
+    /// Unwraps the inner TLV state
+    pub fn into_inner(self) -> S {
+        self.state
+    }
+
+    /// Get the range of the value within the TLV data for the given
+    /// discriminator and repetition number
+    pub fn get_value_range(
+        &self,
+        discriminator: ArrayDiscriminator,
+        repetition_number: usize,
+    ) -> Result<Range<usize>, ProgramError> {
+        self.index
+            .get(AsRef::<[u8; ArrayDiscriminator::LENGTH]>::as_ref(
+                &discriminator,
+            ))
+            .and_then(|ranges| ranges.get(repetition_number))
+            .cloned()
+            .ok_or_else(|| TlvError::TypeNotFound.into())
+    }
+}
+impl<S: TlvState> TlvState for IndexedTlvState<S> {
+    fn get_data(&self) -> &[u8] {
+        self.state.get_data()
+    }
+
+    fn get_layout(&self) -> TlvLayout {
+        self.state.get_layout()
+    }
+
+    fn get_value_with_repetition<V: SplDiscriminate + Pod>(
+        &self,
+        repetition_number: usize,
+    ) -> Result<&V, ProgramError> {
+        let data = self.get_bytes_with_repetition::<V>(repetition_number)?;
+        pod_from_bytes::<V>(data)
+    }
+
+    fn get_variable_len_value_with_repetition<V: SplDiscriminate + VariableLenPack>(
+        &self,
+        repetition_number: usize,
+    ) -> Result<V, ProgramError> {
+        let data = self.get_bytes_with_repetition::<V>(repetition_number)?;
+        V::unpack_from_slice(data)
+    }
+
+    fn get_bytes_with_repetition<V: SplDiscriminate + ?Sized>(
+        &self,
+        repetition_number: usize,
+    ) -> Result<&[u8], ProgramError> {
+        let range = self.get_value_range(V::SPL_DISCRIMINATOR, repetition_number)?;
+        Ok(&self.get_data()[range])
+    }
+
+    fn count<V: SplDiscriminate>(&self) -> Result<usize, ProgramError> {
+        Ok(self
+            .index
+            .get(AsRef::<[u8; ArrayDiscriminator::LENGTH]>::as_ref(
+                &V::SPL_DISCRIMINATOR,
+            ))
+            .map_or(0, Vec::len))
+    }
+}
+
+impl IndexedTlvState<TlvStateMut<'_>> {
+    fn reindex(&mut self) -> Result<(), ProgramError> {
+        self.index = build_index(&self.state)?;
+        Ok(())
+    }
+
+    /// Unpack a portion of the TLV data as the desired type that allows
+    /// modifying the type for the entry number specified
+    pub fn get_value_with_repetition_mut<V: SplDiscriminate + Pod>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<&mut V, ProgramError> {
+        let data = self.get_bytes_with_repetition_mut::<V>(repetition_number)?;
+        pod_from_bytes_mut::<V>(data)
+    }
+
+    /// Unpack a portion of the TLV data as the desired type that allows
+    /// modifying the type for the first entry found
+    pub fn get_first_value_mut<V: SplDiscriminate + Pod>(
+        &mut self,
+    ) -> Result<&mut V, ProgramError> {
+        self.get_value_with_repetition_mut::<V>(0)
+    }
+
+    /// Unpack a portion of the TLV data as mutable bytes for the entry number
+    /// specified
+    pub fn get_bytes_with_repetition_mut<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        let range = self.get_value_range(V::SPL_DISCRIMINATOR, repetition_number)?;
+        Ok(&mut self.state.get_data_mut()[range])
+    }
+
+    /// Unpack a portion of the TLV data as mutable bytes for the first entry
+    /// found
+    pub fn get_first_bytes_mut<V: SplDiscriminate>(&mut self) -> Result<&mut [u8], ProgramError> {
+        self.get_bytes_with_repetition_mut::<V>(0)
+    }
+
+    /// Writes the checksum entry of the inner state, then update the index.
+    /// See `TlvStateMut::update_checksum` for more information.
+    pub fn update_checksum(&mut self) -> Result<(), ProgramError> {
+        self.state.update_checksum()?;
+        self.reindex()
+    }
+
+    /// Packs a variable-length value into its appropriate data segment, where
+    /// repeating discriminators _are_ allowed
+    pub fn pack_variable_len_value_with_repetition<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+        repetition_number: usize,
+    ) -> Result<(), ProgramError> {
+        // goes through the inner state to keep its checksum up to date
+        self.state
+            .pack_variable_len_value_with_repetition(value, repetition_number)
+    }
+
+    /// Allocate the given number of bytes for the given `SplDiscriminate`,
+    /// then update the index. See `TlvStateMut::alloc` for more information.
+    ///
+    /// If the checksum is enabled, call `update_checksum` after writing to the
+    /// returned slice.
+    pub fn alloc<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        allow_repetition: bool,
+    ) -> Result<(&mut [u8], usize), ProgramError> {
+        let (_, repetition_number) = self.state.alloc::<V>(length, allow_repetition)?;
+        self.reindex()?;
+        let data = self.get_bytes_with_repetition_mut::<V>(repetition_number)?;
+        Ok((data, repetition_number))
+    }
+
+    /// Packs the default TLV data into the first open slot in the data
+    /// buffer, then update the index. See `TlvStateMut::init_value` for more
+    /// information.
+    pub fn init_value<V: SplDiscriminate + Pod + Default>(
+        &mut self,
+        allow_repetition: bool,
+    ) -> Result<(&mut V, usize), ProgramError> {
+        let (_, repetition_number) = self.state.init_value::<V>(allow_repetition)?;
+        self.reindex()?;
+        let value = self.get_value_with_repetition_mut::<V>(repetition_number)?;
+        Ok((value, repetition_number))
+    }
+
+    /// Allocates and serializes a new TLV entry from a `VariableLenPack`
+    /// type, then update the index
+    pub fn alloc_and_pack_variable_len_entry<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+        allow_repetition: bool,
+    ) -> Result<usize, ProgramError> {
+        let repetition_number = self
+            .state
+            .alloc_and_pack_variable_len_entry(value, allow_repetition)?;
+        self.reindex()?;
+        Ok(repetition_number)
+    }
+
+    /// Reallocate the given number of bytes for the given `SplDiscriminate`,
+    /// then update the index. See `TlvStateMut::realloc_with_repetition` for
+    /// more information.
+    ///
+    /// If the checksum is enabled, call `update_checksum` after writing to the
+    /// returned slice.
+    pub fn realloc_with_repetition<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        repetition_number: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        self.state
+            .realloc_with_repetition::<V>(length, repetition_number)?;
+        self.reindex()?;
+        self.get_bytes_with_repetition_mut::<V>(repetition_number)
+    }
+
+    /// Reallocate the given number of bytes for the given `SplDiscriminate`,
+    /// where no repeating discriminators are allowed
+    pub fn realloc_first<V: SplDiscriminate>(
+        &mut self,
+        length: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        self.realloc_with_repetition::<V>(length, 0)
+    }
+
+    /// Remove the entry for the given `SplDiscriminate` and repetition
+    /// number, then update the index. See `TlvStateMut::remove` for more
+    /// information.
+    pub fn remove<V: SplDiscriminate>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<usize, ProgramError> {
+        let removed_bytes = self.state.remove::<V>(repetition_number)?;
+        self.reindex()?;
+        Ok(removed_bytes)
+    }
+}
+
+fn build_index<S: TlvState>(
+    state: &S,
+) -> Result<HashMap<[u8; ArrayDiscriminator::LENGTH], Vec<Range<usize>>>, ProgramError> {
+    let layout = state.get_layout();
+    let mut index = HashMap::<_, Vec<_>>::new();
+    for (discriminator, start, value_end, _) in get_all_entry_bounds(&layout, state.get_data())? {
+        let value_start = start.saturating_add(layout.base_len());
+        index
+            .entry(discriminator.into())
+            .or_default()
+            .push(value_start..value_end);
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::state::TlvStateBorrowed, bytemuck::Zeroable};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValue {
+        data: [u8; 32],
+    }
+    impl SplDiscriminate for TestValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestSmallValue {
+        data: [u8; 3],
+    }
+    impl SplDiscriminate for TestSmallValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[test]
+    fn indexed_lookups() {
+        let account_size = TlvStateBorrowed::get_base_len() * 4 + 32 * 2 + 3 + 10;
+        let mut buffer = vec![0; account_size];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        state.init_value::<TestValue>(true).unwrap().0.data = [1; 32];
+        state.init_value::<TestSmallValue>(false).unwrap().0.data = [2; 3];
+        state.init_value::<TestValue>(true).unwrap().0.data = [3; 32];
+
+        let plain = TlvStateBorrowed::unpack(&buffer).unwrap();
+        let indexed = IndexedTlvState::new(TlvStateBorrowed::unpack(&buffer).unwrap()).unwrap();
+        assert_eq!(
+            indexed.get_first_value::<TestValue>().unwrap(),
+            plain.get_first_value::<TestValue>().unwrap()
+        );
+        assert_eq!(
+            indexed.get_value_with_repetition::<TestValue>(1).unwrap(),
+            plain.get_value_with_repetition::<TestValue>(1).unwrap()
+        );
+        assert_eq!(
+            indexed.get_first_bytes::<TestSmallValue>().unwrap(),
+            plain.get_first_bytes::<TestSmallValue>().unwrap()
+        );
+        assert_eq!(indexed.count::<TestValue>().unwrap(), 2);
+        assert_eq!(
+            indexed
+                .get_value_with_repetition::<TestValue>(2)
+                .unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+        assert_eq!(
+            indexed.get_value_range(TestSmallValue::SPL_DISCRIMINATOR, 0),
+            Ok(56..59)
+        );
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestVariableLen {
+        data: Vec<u8>,
+    }
+    impl SplDiscriminate for TestVariableLen {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([3; ArrayDiscriminator::LENGTH]);
+    }
+    impl VariableLenPack for TestVariableLen {
+        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+            dst[..self.data.len()].copy_from_slice(&self.data);
+            Ok(())
+        }
+        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+            Ok(Self { data: src.to_vec() })
+        }
+        fn get_packed_len(&self) -> Result<usize, ProgramError> {
+            Ok(self.data.len())
+        }
+    }
+
+    #[test]
+    fn indexed_checksum() {
+        let mut buffer = vec![0; 256];
+        let state = TlvStateMut::unpack(&mut buffer).unwrap().with_checksum();
+        let mut indexed = IndexedTlvState::new(state).unwrap();
+        let _ = indexed.init_value::<TestValue>(false).unwrap();
+        TlvStateBorrowed::unpack_verified(indexed.get_data()).unwrap();
+        let value = TestVariableLen { data: vec![2; 4] };
+        indexed
+            .alloc_and_pack_variable_len_entry(&value, false)
+            .unwrap();
+        TlvStateBorrowed::unpack_verified(indexed.get_data()).unwrap();
+        let value = TestVariableLen { data: vec![3; 4] };
+        indexed
+            .pack_variable_len_value_with_repetition(&value, 0)
+            .unwrap();
+        TlvStateBorrowed::unpack_verified(indexed.get_data()).unwrap();
+        assert_eq!(
+            indexed
+                .get_first_variable_len_value::<TestVariableLen>()
+                .unwrap(),
+            value
+        );
+
+        // writing to an allocated slice needs an explicit update
+        indexed.alloc::<TestSmallValue>(3, false).unwrap().0[0] = 4;
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(indexed.get_data()).unwrap_err(),
+            TlvError::ChecksumMismatch.into()
+        );
+        indexed.update_checksum().unwrap();
+        TlvStateBorrowed::unpack_verified(indexed.get_data()).unwrap();
+        assert_eq!(
+            indexed.get_first_value::<TestSmallValue>().unwrap().data,
+            [4, 0, 0]
+        );
+    }
+
+    #[test]
+    fn reindex_after_realloc() {
+        let account_size = TlvStateBorrowed::get_base_len() * 3 + 32 + 3 + 20;
+        let mut buffer = vec![0; account_size];
+        let mut indexed = IndexedTlvState::new(TlvStateMut::unpack(&mut buffer).unwrap()).unwrap();
+        indexed.init_value::<TestSmallValue>(false).unwrap().0.data = [2; 3];
+        indexed.init_value::<TestValue>(false).unwrap().0.data = [1; 32];
+        let small_range = indexed.get_value_range(TestSmallValue::SPL_DISCRIMINATOR, 0);
+
+        // moves the second entry forward, then back
+        indexed.realloc_first::<TestSmallValue>(13).unwrap();
+        assert_eq!(
+            indexed.get_first_value::<TestValue>().unwrap().data,
+            [1; 32]
+        );
+        indexed.get_first_value_mut::<TestValue>().unwrap().data[0] = 9;
+        indexed.realloc_first::<TestSmallValue>(3).unwrap();
+        assert_eq!(
+            indexed.get_value_range(TestSmallValue::SPL_DISCRIMINATOR, 0),
+            small_range
+        );
+        assert_eq!(
+            indexed.get_first_value::<TestValue>().unwrap().data[..2],
+            [9, 1]
+        );
+
+        // removed entries are dropped from the index
+        indexed.remove::<TestSmallValue>(0).unwrap();
+        assert_eq!(
+            indexed.get_first_bytes::<TestSmallValue>().unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+        assert_eq!(
+            indexed.get_first_value::<TestValue>().unwrap().data[..2],
+            [9, 1]
+        );
+        let state = indexed.into_inner();
+        assert_eq!(
+            state.get_first_value::<TestValue>().unwrap().data[..2],
+            [9, 1]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Module describing the widths of the type and length portions of a
+//! Type-Length-Value structure
+use {
+    crate::{error::TlvError, length::LengthSize},
+    solana_program_error::ProgramError,
+    spl_discriminator::ArrayDiscriminator,
+};
+
+/// Layout of the entries in a TLV structure
+///
+/// By default, the "type" is an 8-byte `ArrayDiscriminator` and the "length"
+/// is a 4-byte little-endian `u32`. Other layouts use a smaller discriminator,
+/// in which case only the first `discriminator_size` bytes of an
+/// `ArrayDiscriminator` are stored, and the rest must be zero. For example,
+/// a Token-2022-style layout with a 2-byte type and a 2-byte length is
+/// `TlvLayout::new(2, LengthSize::U16)`, where a discriminator created with
+/// `ArrayDiscriminator::from(u64)` matches the little-endian `u16` type.
+///
+/// Layouts can also opt into padded entries with `with_value_alignment`, so
+/// that values can be borrowed as types with an alignment greater than 1,
+/// such as `#[repr(C)]` structs holding a native `u64`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TlvLayout {
+    discriminator_size: usize,
+    length_size: LengthSize,
+    value_alignment: usize,
+}
+impl TlvLayout {
+    /// The default layout, with an 8-byte discriminator and 4-byte length
+    pub const DEFAULT: Self = Self::new(ArrayDiscriminator::LENGTH, LengthSize::U32);
+
+    /// Creates a new layout from the discriminator and length sizes
+    ///
+    /// Panics if the discriminator size is zero or larger than
+    /// `ArrayDiscriminator::LENGTH`
+    pub const fn new(discriminator_size: usize, length_size: LengthSize) -> Self {
+        assert!(
+            discriminator_size > 0 && discriminator_size <= ArrayDiscriminator::LENGTH,
+            "Discriminator size must be between 1 and 8 bytes"
+        );
+        Self {
+            discriminator_size,
+            length_size,
+            value_alignment: 1,
+        }
+    }
+
+    /// Creates the same layout where every value starts at an offset that is
+    /// a multiple of `value_alignment`.
+    ///
+    /// Zero padding is added after the length, so that the header takes a
+    /// multiple of the alignment, and after the value, so that the next entry
+    /// also starts at a multiple of the alignment. The written length does not
+    /// include any padding, and the padding of every entry is fully determined
+    /// by the layout, so the data must always be read with the same layout.
+    ///
+    /// Offsets are relative to the start of the TLV data, so the data itself
+    /// must start at an address with the same alignment to borrow values
+    /// zero-copy. For example, account data is aligned to 8 bytes on-chain.
+    ///
+    /// Panics if the alignment is not a power of two
+    pub const fn with_value_alignment(self, value_alignment: usize) -> Self {
+        assert!(
+            value_alignment.is_power_of_two(),
+            "Value alignment must be a power of two"
+        );
+        Self {
+            value_alignment,
+            ..self
+        }
+    }
+
+    /// Number of bytes taken by the discriminator
+    pub const fn discriminator_size(&self) -> usize {
+        self.discriminator_size
+    }
+
+    /// Number of bytes taken by the length
+    pub const fn length_size(&self) -> LengthSize {
+        self.length_size
+    }
+
+    /// Alignment of the start of every value, 1 if values are not padded
+    pub const fn value_alignment(&self) -> usize {
+        self.value_alignment
+    }
+
+    /// Size of the discriminator and length, along with any padding, which is
+    /// the base size required for every entry
+    pub const fn base_len(&self) -> usize {
+        self.padded_len(
+            self.discriminator_size
+                .saturating_add(self.length_size.size()),
+        )
+    }
+
+    /// Size taken by a value of the given length, along with any padding
+    pub const fn padded_len(&self, length: usize) -> usize {
+        length.saturating_add(self.value_alignment - 1) & !(self.value_alignment - 1)
+    }
+
+    /// Total size of an entry holding a value of the given length
+    pub const fn entry_len(&self, length: usize) -> usize {
+        self.base_len().saturating_add(self.padded_len(length))
+    }
+
+    /// Reads a discriminator from the start of the given bytes, padding it
+    /// with zeroes up to `ArrayDiscriminator::LENGTH`
+    pub fn read_discriminator(&self, src: &[u8]) -> Result<ArrayDiscriminator, ProgramError> {
+        let src = src
+            .get(..self.discriminator_size)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let mut bytes = [0u8; ArrayDiscriminator::LENGTH];
+        bytes[..self.discriminator_size].copy_from_slice(src);
+        Ok(ArrayDiscriminator::new(bytes))
+    }
+
+    /// Writes a discriminator to the start of the given bytes, failing if it
+    /// does not fit in the layout's discriminator size
+    pub fn write_discriminator(
+        &self,
+        discriminator: ArrayDiscriminator,
+        dst: &mut [u8],
+    ) -> Result<(), ProgramError> {
+        if !self.fits(discriminator) {
+            return Err(TlvError::DiscriminatorTooLarge.into());
+        }
+        let dst = dst
+            .get_mut(..self.discriminator_size)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        dst.copy_from_slice(&discriminator.as_slice()[..self.discriminator_size]);
+        Ok(())
+    }
+
+    /// Checks if the discriminator can be stored in this layout without losing
+    /// any information
+    pub fn fits(&self, discriminator: ArrayDiscriminator) -> bool {
+        discriminator.as_slice()[self.discriminator_size..]
+            .iter()
+            .all(|&x| x == 0)
+    }
+}
+impl Default for TlvLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_write_compact_layout() {
+        let layout = TlvLayout::new(2, LengthSize::U16);
+        assert_eq!(layout.base_len(), 4);
+
+        let discriminator = ArrayDiscriminator::from(0x0102);
+        let mut buffer = [0; 4];
+        layout
+            .write_discriminator(discriminator, &mut buffer)
+            .unwrap();
+        layout
+            .length_size()
+            .write(0x0304, &mut buffer[layout.discriminator_size()..])
+            .unwrap();
+        assert_eq!(buffer, [2, 1, 4, 3]);
+        assert_eq!(layout.read_discriminator(&buffer).unwrap(), discriminator);
+        assert_eq!(layout.length_size().read(&buffer[2..]).unwrap(), 0x0304);
+
+        // values that don't fit
+        assert_eq!(
+            layout
+                .write_discriminator(ArrayDiscriminator::from(0x010000), &mut buffer)
+                .unwrap_err(),
+            TlvError::DiscriminatorTooLarge.into()
+        );
+        assert_eq!(
+            layout
+                .length_size()
+                .write(0x010000, &mut buffer[2..])
+                .unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+        assert_eq!(
+            layout.length_size().read(&buffer[3..]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    fn padded_layout() {
+        let layout = TlvLayout::DEFAULT.with_value_alignment(8);
+        assert_eq!(layout.base_len(), 16);
+        assert_eq!(layout.padded_len(0), 0);
+        assert_eq!(layout.padded_len(1), 8);
+        assert_eq!(layout.padded_len(16), 16);
+        assert_eq!(layout.entry_len(9), 32);
+
+        let layout = TlvLayout::new(2, LengthSize::U16).with_value_alignment(16);
+        assert_eq!(layout.base_len(), 16);
+        assert_eq!(TlvLayout::DEFAULT.entry_len(9), 21);
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_value_alignment() {
+        let _ = TlvLayout::DEFAULT.with_value_alignment(12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_discriminator_size() {
+        let _ = TlvLayout::new(9, LengthSize::U8);
+    }
+}
@@ -23,3 +23,50 @@ impl TryFrom<usize> for Length {
             .map_err(|_| ProgramError::AccountDataTooSmall)
     }
 }
+
+/// Number of bytes used to encode the length in a TLV structure, always
+/// little-endian
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LengthSize {
+    /// 1-byte length, `u8`
+    U8 = 1,
+    /// 2-byte length, `u16`
+    U16 = 2,
+    /// 4-byte length, `u32`, used by `Length`
+    #[default]
+    U32 = 4,
+    /// 8-byte length, `u64`
+    U64 = 8,
+}
+impl LengthSize {
+    /// Number of bytes taken by the length
+    pub const fn size(&self) -> usize {
+        *self as usize
+    }
+
+    /// Reads a length of this size from the start of the given bytes
+    pub fn read(&self, src: &[u8]) -> Result<usize, ProgramError> {
+        let src = src
+            .get(..self.size())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let mut bytes = [0u8; 8];
+        bytes[..self.size()].copy_from_slice(src);
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| ProgramError::AccountDataTooSmall)
+    }
+
+    /// Writes a length of this size to the start of the given bytes, failing
+    /// if the length cannot be represented with this size
+    pub fn write(&self, length: usize, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let dst = dst
+            .get_mut(..self.size())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let bytes = u64::try_from(length)
+            .map_err(|_| ProgramError::AccountDataTooSmall)?
+            .to_le_bytes();
+        if bytes[self.size()..].iter().any(|&x| x != 0) {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        dst.copy_from_slice(&bytes[..self.size()]);
+        Ok(())
+    }
+}
@@ -5,14 +5,26 @@
 #![deny(missing_docs)]
 #![cfg_attr(not(test), forbid(unsafe_code))]
 
+pub mod account;
+pub mod builder;
+pub mod checksum;
+pub mod diff;
 pub mod error;
+pub mod header_tail;
+pub mod indexed;
+pub mod layout;
 pub mod length;
+pub mod migrate;
+pub mod registry;
 pub mod state;
+pub mod stream;
 pub mod variable_len_pack;
 
 // Export current sdk types for downstream users building with a different sdk
 // version
-// Expose derive macro on feature flag
+// Expose derive macros on feature flag
 #[cfg(feature = "derive")]
-pub use spl_type_length_value_derive::SplBorshVariableLenPack;
+pub use spl_type_length_value_derive::{
+    SplBorshVariableLenPack, SplPodHeaderTail, SplVariableLenPack,
+};
 pub use {solana_account_info, solana_decode_error, solana_program_error};
@@ -0,0 +1,38 @@
+//! The [`TlvMigrate`] versioning trait.
+
+use {solana_program_error::ProgramError, spl_discriminator::SplDiscriminate};
+
+/// Trait for a TLV value that replaces a previous version of itself.
+///
+/// Versions of a value can be told apart in one of two ways:
+///
+/// * every version is a separate type with its own discriminator, usually
+///   derived from a version-suffixed hash input, such as `"my_value:v1"` and
+///   `"my_value:v2"`
+/// * every version shares the same discriminator, and the first byte of the
+///   value holds its version, set with `VERSION`
+///
+/// When upgrading, entries of the previous version are rewritten in place
+/// with the new discriminator and value, using `TlvStateMut::migrate_all`.
+/// With a version byte, only entries whose version byte is lower than
+/// `VERSION` are rewritten.
+///
+/// To go through several versions, migrate from each one to the next in
+/// order.
+pub trait TlvMigrate: SplDiscriminate {
+    /// The previous version of the value
+    type Previous: SplDiscriminate;
+
+    /// The version written to the first byte of the value, required if the
+    /// value shares its discriminator with the previous version
+    const VERSION: Option<u8> = None;
+
+    /// Gets the length of the new value, given the bytes of the previous one
+    fn get_migrated_len(previous: &[u8]) -> Result<usize, ProgramError>;
+
+    /// Writes the new value into the given slice, which is exactly as long as
+    /// `get_migrated_len`, given the bytes of the previous one.
+    ///
+    /// If `VERSION` is set, the first byte is overwritten with it afterwards.
+    fn migrate(previous: &[u8], dst: &mut [u8]) -> Result<(), ProgramError>;
+}
@@ -0,0 +1,205 @@
+//! Registry of known TLV types, to render TLV data in a human-readable way
+
+use {
+    crate::variable_len_pack::VariableLenPack,
+    bytemuck::Pod,
+    solana_program_error::ProgramError,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_pod::bytemuck::pod_from_bytes,
+    std::{collections::HashMap, fmt::Debug},
+};
+
+/// Function decoding the value bytes of a registered type into a
+/// human-readable string
+pub type TlvDecodeFn = fn(&[u8]) -> Result<String, ProgramError>;
+
+fn decode_pod<V: Pod + Debug>(data: &[u8]) -> Result<String, ProgramError> {
+    pod_from_bytes::<V>(data).map(|value| format!("{:?}", value))
+}
+
+fn decode_variable_len<V: VariableLenPack + Debug>(data: &[u8]) -> Result<String, ProgramError> {
+    V::unpack_from_slice(data).map(|value| format!("{:?}", value))
+}
+
+/// Formats bytes as a lowercase hex string, prefixed by `0x`
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + data.len() * 2);
+    hex.push_str("0x");
+    for byte in data {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+/// Maps discriminators to the name of their type and a function decoding
+/// their value, used by `TlvState::dump`
+#[derive(Clone, Debug, Default)]
+pub struct TlvRegistry {
+    types: HashMap<[u8; ArrayDiscriminator::LENGTH], (String, TlvDecodeFn)>,
+}
+
+impl TlvRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a type with the given name and decode function, replacing
+    /// any type registered with the same discriminator
+    pub fn register(
+        &mut self,
+        discriminator: ArrayDiscriminator,
+        name: &str,
+        decode: TlvDecodeFn,
+    ) -> &mut Self {
+        self.types
+            .insert(discriminator.into(), (name.to_string(), decode));
+        self
+    }
+
+    /// Registers a Pod type, decoded with its `Debug` implementation
+    pub fn register_pod<V: SplDiscriminate + Pod + Debug>(&mut self) -> &mut Self {
+        self.register(
+            V::SPL_DISCRIMINATOR,
+            std::any::type_name::<V>(),
+            decode_pod::<V>,
+        )
+    }
+
+    /// Registers a variable-length type, decoded with its `Debug`
+    /// implementation
+    pub fn register_variable_len<V: SplDiscriminate + VariableLenPack + Debug>(
+        &mut self,
+    ) -> &mut Self {
+        self.register(
+            V::SPL_DISCRIMINATOR,
+            std::any::type_name::<V>(),
+            decode_variable_len::<V>,
+        )
+    }
+
+    /// Gets the name registered for the discriminator
+    pub fn get_name(&self, discriminator: &ArrayDiscriminator) -> Option<&str> {
+        self.types
+            .get(AsRef::<[u8; ArrayDiscriminator::LENGTH]>::as_ref(
+                discriminator,
+            ))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Decodes the value bytes with the function registered for the
+    /// discriminator, or `None` if the discriminator is unknown
+    pub fn decode(
+        &self,
+        discriminator: &ArrayDiscriminator,
+        data: &[u8],
+    ) -> Option<Result<String, ProgramError>> {
+        self.types
+            .get(AsRef::<[u8; ArrayDiscriminator::LENGTH]>::as_ref(
+                discriminator,
+            ))
+            .map(|(_, decode)| decode(data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::{TlvState, TlvStateMut},
+        bytemuck::Zeroable,
+    };
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValue {
+        data: [u8; 2],
+    }
+    impl SplDiscriminate for TestValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct TestVariableLen {
+        data: String,
+    }
+    impl SplDiscriminate for TestVariableLen {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2; ArrayDiscriminator::LENGTH]);
+    }
+    impl VariableLenPack for TestVariableLen {
+        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+            dst.copy_from_slice(self.data.as_bytes());
+            Ok(())
+        }
+        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+            let data = std::str::from_utf8(src)
+                .map_err(|_| ProgramError::InvalidAccountData)?
+                .to_string();
+            Ok(Self { data })
+        }
+        fn get_packed_len(&self) -> Result<usize, ProgramError> {
+            Ok(self.data.len())
+        }
+    }
+
+    #[test]
+    fn dump_entries() {
+        let mut registry = TlvRegistry::new();
+        registry
+            .register_pod::<TestValue>()
+            .register_variable_len::<TestVariableLen>();
+        assert_eq!(
+            registry.get_name(&TestValue::SPL_DISCRIMINATOR),
+            Some(std::any::type_name::<TestValue>())
+        );
+
+        let mut buffer = vec![0; 64];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        state.init_value::<TestValue>(true).unwrap().0.data = [1, 2];
+        state
+            .alloc_and_pack_variable_len_entry(
+                &TestVariableLen {
+                    data: "hi".to_string(),
+                },
+                false,
+            )
+            .unwrap();
+        state
+            .alloc_and_pack_variable_len_entry(
+                &TestVariableLen {
+                    data: "hi".to_string(),
+                },
+                true,
+            )
+            .unwrap();
+        state
+            .get_bytes_with_repetition_mut::<TestVariableLen>(1)
+            .unwrap()
+            .copy_from_slice(&[0xff, 0xfe]);
+        state.alloc::<TestValue>(3, true).unwrap().0.fill(0xab);
+
+        let value_name = std::any::type_name::<TestValue>();
+        let variable_len_name = std::any::type_name::<TestVariableLen>();
+        assert_eq!(
+            state.dump(&registry).unwrap(),
+            format!(
+                "offset 0, length 2: {value_name} #0 = TestValue {{ data: [1, 2] }}\n\
+                 offset 14, length 2: {variable_len_name} #0 = TestVariableLen {{ data: \"hi\" }}\n\
+                 offset 28, length 2: {variable_len_name} #1 = 0xfffe (failed to decode: An account's data contents was invalid)\n\
+                 offset 42, length 3: {value_name} #1 = 0xababab (failed to decode: The arguments provided to a program instruction were invalid)\n"
+            )
+        );
+
+        let mut registry = TlvRegistry::new();
+        registry.register_pod::<TestValue>();
+        let mut buffer = vec![0; 32];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        state.alloc::<TestVariableLen>(3, false).unwrap().0.fill(1);
+        assert_eq!(
+            state.dump(&registry).unwrap(),
+            "offset 0, length 3: unknown 0x0202020202020202 #0 = 0x010101\n"
+        );
+    }
+}
@@ -1,19 +1,32 @@
 //! Type-length-value structure definition and manipulation
 
 use {
-    crate::{error::TlvError, length::Length, variable_len_pack::VariableLenPack},
+    crate::{
+        account::TlvAccount,
+        checksum::TlvChecksum,
+        error::{TlvDataError, TlvError},
+        header_tail::{header_tail_from_bytes, header_tail_from_bytes_mut, PodHeaderTail},
+        layout::TlvLayout,
+        migrate::TlvMigrate,
+        registry::{to_hex, TlvRegistry},
+        variable_len_pack::VariableLenPack,
+    },
     bytemuck::Pod,
-    solana_account_info::AccountInfo,
     solana_program_error::ProgramError,
+    solana_rent::Rent,
     spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
-    spl_pod::bytemuck::{pod_from_bytes, pod_from_bytes_mut},
-    std::{cmp::Ordering, mem::size_of},
+    spl_pod::bytemuck::{pod_bytes_of, pod_from_bytes, pod_from_bytes_mut, pod_slice_to_bytes},
+    std::{cmp::Ordering, mem::size_of, ops::Range},
 };
 
 /// Get the current `TlvIndices` from the current spot
-const fn get_indices_unchecked(type_start: usize, value_repetition_number: usize) -> TlvIndices {
-    let length_start = type_start.saturating_add(size_of::<ArrayDiscriminator>());
-    let value_start = length_start.saturating_add(size_of::<Length>());
+const fn get_indices_unchecked(
+    layout: &TlvLayout,
+    type_start: usize,
+    value_repetition_number: usize,
+) -> TlvIndices {
+    let length_start = type_start.saturating_add(layout.discriminator_size());
+    let value_start = type_start.saturating_add(layout.base_len());
     TlvIndices {
         type_start,
         length_start,
@@ -32,119 +45,301 @@ struct TlvIndices {
     pub value_repetition_number: usize,
 }
 
-fn get_indices(
+/// Reads the header of the entry starting at `start_index`, returning its
+/// discriminator, the start and end indices of its value, and the end index
+/// of the entry including any padding, or `None` if the rest of the buffer is
+/// uninitialized
+fn get_entry_bounds(
+    layout: &TlvLayout,
     tlv_data: &[u8],
-    value_discriminator: ArrayDiscriminator,
-    init: bool,
-    repetition_number: Option<usize>,
-) -> Result<TlvIndices, ProgramError> {
-    let mut current_repetition_number = 0;
+    start_index: usize,
+) -> Result<Option<(ArrayDiscriminator, usize, usize, usize)>, TlvDataError> {
+    // This function is not concerned with repetitions, so we can just
+    // arbitrarily pass `0` here
+    let tlv_indices = get_indices_unchecked(layout, start_index, 0);
+    let truncated_header = TlvDataError::TruncatedHeader {
+        offset: tlv_indices.type_start,
+    };
+    if tlv_data.len() < tlv_indices.length_start {
+        // we got to the end, but there might be some uninitialized data after
+        let remainder = &tlv_data[tlv_indices.type_start..];
+        if remainder.iter().all(|&x| x == 0) {
+            return Ok(None);
+        } else {
+            return Err(truncated_header);
+        }
+    }
+    let discriminator = layout
+        .read_discriminator(&tlv_data[tlv_indices.type_start..tlv_indices.length_start])
+        .map_err(|_| truncated_header.clone())?;
+    if discriminator == ArrayDiscriminator::UNINITIALIZED {
+        return Ok(None);
+    }
+    if tlv_data.len() < tlv_indices.value_start {
+        // not enough bytes to store the length, malformed
+        return Err(truncated_header);
+    }
+    let length = layout
+        .length_size()
+        .read(&tlv_data[tlv_indices.length_start..tlv_indices.value_start])
+        .unwrap_or(usize::MAX);
+    let value_end_index = tlv_indices.value_start.saturating_add(length);
+    let entry_end_index = tlv_indices
+        .value_start
+        .saturating_add(layout.padded_len(length));
+    if entry_end_index > tlv_data.len() {
+        // value blows past the size of the slice, malformed
+        return Err(TlvDataError::LengthOverflow {
+            offset: tlv_indices.type_start,
+            length,
+        });
+    }
+    Ok(Some((
+        discriminator,
+        tlv_indices.value_start,
+        value_end_index,
+        entry_end_index,
+    )))
+}
+
+/// Reads the headers of all entries, returning the discriminator, the start
+/// index, the value end index, and the end index including padding of each one
+pub(crate) fn get_all_entry_bounds(
+    layout: &TlvLayout,
+    tlv_data: &[u8],
+) -> Result<Vec<(ArrayDiscriminator, usize, usize, usize)>, ProgramError> {
+    let mut entries = vec![];
     let mut start_index = 0;
-    while start_index < tlv_data.len() {
-        let tlv_indices = get_indices_unchecked(start_index, current_repetition_number);
-        if tlv_data.len() < tlv_indices.value_start {
-            return Err(ProgramError::InvalidAccountData);
+    while let Some((discriminator, _, value_end, entry_end)) =
+        get_entry_bounds(layout, tlv_data, start_index)?
+    {
+        entries.push((discriminator, start_index, value_end, entry_end));
+        start_index = entry_end;
+    }
+    Ok(entries)
+}
+
+/// Returns the repetition number for the next entry with the given
+/// discriminator, keeping track of the number of entries seen so far
+fn next_repetition_number(
+    repetitions: &mut Vec<(ArrayDiscriminator, usize)>,
+    discriminator: ArrayDiscriminator,
+) -> usize {
+    if let Some((_, count)) = repetitions.iter_mut().find(|(d, _)| *d == discriminator) {
+        let repetition_number = *count;
+        *count += 1;
+        repetition_number
+    } else {
+        repetitions.push((discriminator, 1));
+        0
+    }
+}
+
+/// Iterator over all entries in a TLV buffer, created by [`TlvState::iter`]
+///
+/// Each item contains the entry's discriminator, its repetition number, the
+/// index in the buffer where its value starts, and the value's bytes. The
+/// iterator stops at the first uninitialized entry, and yields a single error
+/// if the buffer is malformed.
+#[derive(Debug)]
+pub struct TlvIter<'data> {
+    layout: TlvLayout,
+    data: &'data [u8],
+    start_index: usize,
+    repetitions: Vec<(ArrayDiscriminator, usize)>,
+    done: bool,
+}
+impl<'data> TlvIter<'data> {
+    fn new(layout: TlvLayout, data: &'data [u8]) -> Self {
+        Self {
+            layout,
+            data,
+            start_index: 0,
+            repetitions: vec![],
+            done: false,
         }
-        let discriminator = ArrayDiscriminator::try_from(
-            &tlv_data[tlv_indices.type_start..tlv_indices.length_start],
-        )?;
-        if discriminator == value_discriminator {
-            if let Some(desired_repetition_number) = repetition_number {
-                if current_repetition_number == desired_repetition_number {
-                    return Ok(tlv_indices);
-                }
+    }
+
+    /// Index where the next entry would start, which is the end of all
+    /// initialized entries once the iterator is exhausted
+    fn end_index(&self) -> usize {
+        self.start_index
+    }
+}
+impl<'data> Iterator for TlvIter<'data> {
+    type Item = Result<(ArrayDiscriminator, usize, usize, &'data [u8]), ProgramError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.start_index >= self.data.len() {
+            return None;
+        }
+        match get_entry_bounds(&self.layout, self.data, self.start_index) {
+            Ok(Some((discriminator, value_start, value_end, entry_end))) => {
+                self.start_index = entry_end;
+                let repetition_number =
+                    next_repetition_number(&mut self.repetitions, discriminator);
+                Some(Ok((
+                    discriminator,
+                    repetition_number,
+                    value_start,
+                    &self.data[value_start..value_end],
+                )))
             }
-            current_repetition_number += 1;
-        // got to an empty spot, init here, or error if we're searching, since
-        // nothing is written after an Uninitialized spot
-        } else if discriminator == ArrayDiscriminator::UNINITIALIZED {
-            if init {
-                return Ok(tlv_indices);
-            } else {
-                return Err(TlvError::TypeNotFound.into());
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e.into()))
             }
         }
-        let length =
-            pod_from_bytes::<Length>(&tlv_data[tlv_indices.length_start..tlv_indices.value_start])?;
-        let value_end_index = tlv_indices
-            .value_start
-            .saturating_add(usize::try_from(*length)?);
-        start_index = value_end_index;
-    }
-    Err(ProgramError::InvalidAccountData)
+    }
 }
 
-// This function is doing two separate things at once, and would probably be
-// better served by some custom iterator, but let's leave that for another day.
-fn get_discriminators_and_end_index(
-    tlv_data: &[u8],
-) -> Result<(Vec<ArrayDiscriminator>, usize), ProgramError> {
-    let mut discriminators = vec![];
-    let mut start_index = 0;
-    while start_index < tlv_data.len() {
-        // This function is not concerned with repetitions, so we can just
-        // arbitrarily pass `0` here
-        let tlv_indices = get_indices_unchecked(start_index, 0);
-        if tlv_data.len() < tlv_indices.length_start {
-            // we got to the end, but there might be some uninitialized data after
-            let remainder = &tlv_data[tlv_indices.type_start..];
-            if remainder.iter().all(|&x| x == 0) {
-                return Ok((discriminators, tlv_indices.type_start));
-            } else {
-                return Err(ProgramError::InvalidAccountData);
-            }
+/// Iterator over all entries in a mutable TLV buffer, created by
+/// [`TlvStateMut::iter_mut`]
+///
+/// Yields the same items as [`TlvIter`], but with mutable value slices.
+#[derive(Debug)]
+pub struct TlvIterMut<'data> {
+    layout: TlvLayout,
+    remaining: &'data mut [u8],
+    offset: usize,
+    repetitions: Vec<(ArrayDiscriminator, usize)>,
+    done: bool,
+}
+impl<'data> TlvIterMut<'data> {
+    fn new(layout: TlvLayout, data: &'data mut [u8]) -> Self {
+        Self {
+            layout,
+            remaining: data,
+            offset: 0,
+            repetitions: vec![],
+            done: false,
         }
-        let discriminator = ArrayDiscriminator::try_from(
-            &tlv_data[tlv_indices.type_start..tlv_indices.length_start],
-        )?;
-        if discriminator == ArrayDiscriminator::UNINITIALIZED {
-            return Ok((discriminators, tlv_indices.type_start));
-        } else {
-            if tlv_data.len() < tlv_indices.value_start {
-                // not enough bytes to store the length, malformed
-                return Err(ProgramError::InvalidAccountData);
+    }
+}
+impl<'data> Iterator for TlvIterMut<'data> {
+    type Item = Result<(ArrayDiscriminator, usize, usize, &'data mut [u8]), ProgramError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining.is_empty() {
+            return None;
+        }
+        match get_entry_bounds(&self.layout, self.remaining, 0) {
+            Ok(Some((discriminator, value_start, value_end, entry_end))) => {
+                let remaining = std::mem::take(&mut self.remaining);
+                let (entry, rest) = remaining.split_at_mut(entry_end);
+                self.remaining = rest;
+                let entry_offset = self.offset;
+                self.offset = self.offset.saturating_add(entry_end);
+                let repetition_number =
+                    next_repetition_number(&mut self.repetitions, discriminator);
+                Some(Ok((
+                    discriminator,
+                    repetition_number,
+                    entry_offset.saturating_add(value_start),
+                    &mut entry[value_start..value_end],
+                )))
             }
-            discriminators.push(discriminator);
-            let length = pod_from_bytes::<Length>(
-                &tlv_data[tlv_indices.length_start..tlv_indices.value_start],
-            )?;
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
 
-            let value_end_index = tlv_indices
-                .value_start
-                .saturating_add(usize::try_from(*length)?);
-            if value_end_index > tlv_data.len() {
-                // value blows past the size of the slice, malformed
-                return Err(ProgramError::InvalidAccountData);
+fn get_indices(
+    layout: &TlvLayout,
+    tlv_data: &[u8],
+    value_discriminator: ArrayDiscriminator,
+    init: bool,
+    repetition_number: Option<usize>,
+) -> Result<TlvIndices, ProgramError> {
+    let mut entries = TlvIter::new(*layout, tlv_data);
+    let mut next_repetition_number = 0;
+    for entry in entries.by_ref() {
+        let (discriminator, entry_repetition_number, value_start, _) = entry?;
+        if discriminator == value_discriminator {
+            if repetition_number == Some(entry_repetition_number) {
+                return Ok(get_indices_unchecked(
+                    layout,
+                    value_start.saturating_sub(layout.base_len()),
+                    entry_repetition_number,
+                ));
             }
-            start_index = value_end_index;
+            next_repetition_number = entry_repetition_number.saturating_add(1);
         }
     }
-    Ok((discriminators, start_index))
+    // got to an empty spot, init here, or error if we're searching, since
+    // nothing is written after an Uninitialized spot
+    let tlv_indices = get_indices_unchecked(layout, entries.end_index(), next_repetition_number);
+    if tlv_data.len() < tlv_indices.value_start {
+        Err(ProgramError::InvalidAccountData)
+    } else if init {
+        Ok(tlv_indices)
+    } else {
+        Err(TlvError::TypeNotFound.into())
+    }
 }
 
-fn get_bytes<V: SplDiscriminate>(
+pub(crate) fn get_discriminators_and_end_index(
+    layout: &TlvLayout,
     tlv_data: &[u8],
+) -> Result<(Vec<ArrayDiscriminator>, usize), ProgramError> {
+    let mut entries = TlvIter::new(*layout, tlv_data);
+    let discriminators = entries
+        .by_ref()
+        .map(|entry| entry.map(|(discriminator, ..)| discriminator))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((discriminators, entries.end_index()))
+}
+
+/// Get the range of the value for the given discriminator and repetition
+/// number within the TLV data
+pub(crate) fn get_value_range(
+    layout: &TlvLayout,
+    tlv_data: &[u8],
+    value_discriminator: ArrayDiscriminator,
     repetition_number: usize,
-) -> Result<&[u8], ProgramError> {
+) -> Result<Range<usize>, ProgramError> {
     let TlvIndices {
         type_start: _,
         length_start,
         value_start,
         value_repetition_number: _,
     } = get_indices(
+        layout,
         tlv_data,
-        V::SPL_DISCRIMINATOR,
+        value_discriminator,
         false,
         Some(repetition_number),
     )?;
     // get_indices has checked that tlv_data is long enough to include these
     // indices
-    let length = pod_from_bytes::<Length>(&tlv_data[length_start..value_start])?;
-    let value_end = value_start.saturating_add(usize::try_from(*length)?);
+    let length = layout
+        .length_size()
+        .read(&tlv_data[length_start..value_start])?;
+    let value_end = value_start.saturating_add(length);
     if tlv_data.len() < value_end {
         return Err(ProgramError::InvalidAccountData);
     }
-    Ok(&tlv_data[value_start..value_end])
+    Ok(value_start..value_end)
+}
+
+fn get_bytes<'data, V: SplDiscriminate + ?Sized>(
+    layout: &TlvLayout,
+    tlv_data: &'data [u8],
+    repetition_number: usize,
+) -> Result<&'data [u8], ProgramError> {
+    let range = get_value_range(layout, tlv_data, V::SPL_DISCRIMINATOR, repetition_number)?;
+    Ok(&tlv_data[range])
 }
 
 /// Trait for all TLV state
@@ -155,6 +350,10 @@ fn get_bytes<V: SplDiscriminate>(
 ///   * the "length" is a `Length`, 4 bytes
 ///   * the "value" is a slab of "length" bytes
 ///
+/// These are the widths of the default `TlvLayout`. States unpacked with a
+/// different layout, such as a 2-byte type and 2-byte length, only store the
+/// first bytes of each `ArrayDiscriminator`.
+///
 /// With this structure, it's possible to hold onto any number of entries with
 /// unique discriminators, provided that the total underlying data has enough
 /// bytes for every entry.
@@ -207,13 +406,18 @@ pub trait TlvState {
     /// Get the full buffer containing all TLV data
     fn get_data(&self) -> &[u8];
 
+    /// Get the layout of the type and length of every entry
+    fn get_layout(&self) -> TlvLayout {
+        TlvLayout::DEFAULT
+    }
+
     /// Unpack a portion of the TLV data as the desired Pod type for the entry
     /// number specified
     fn get_value_with_repetition<V: SplDiscriminate + Pod>(
         &self,
         repetition_number: usize,
     ) -> Result<&V, ProgramError> {
-        let data = get_bytes::<V>(self.get_data(), repetition_number)?;
+        let data = get_bytes::<V>(&self.get_layout(), self.get_data(), repetition_number)?;
         pod_from_bytes::<V>(data)
     }
 
@@ -229,7 +433,7 @@ pub trait TlvState {
         &self,
         repetition_number: usize,
     ) -> Result<V, ProgramError> {
-        let data = get_bytes::<V>(self.get_data(), repetition_number)?;
+        let data = get_bytes::<V>(&self.get_layout(), self.get_data(), repetition_number)?;
         V::unpack_from_slice(data)
     }
 
@@ -242,11 +446,11 @@ pub trait TlvState {
     }
 
     /// Unpack a portion of the TLV data as bytes for the entry number specified
-    fn get_bytes_with_repetition<V: SplDiscriminate>(
+    fn get_bytes_with_repetition<V: SplDiscriminate + ?Sized>(
         &self,
         repetition_number: usize,
     ) -> Result<&[u8], ProgramError> {
-        get_bytes::<V>(self.get_data(), repetition_number)
+        get_bytes::<V>(&self.get_layout(), self.get_data(), repetition_number)
     }
 
     /// Unpack a portion of the TLV data as bytes for the first entry found
@@ -254,12 +458,178 @@ pub trait TlvState {
         self.get_bytes_with_repetition::<V>(0)
     }
 
+    /// Unpack a portion of the TLV data as the header and items of a
+    /// `PodHeaderTail` type for the entry number specified, without copying
+    fn get_header_tail_with_repetition<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &self,
+        repetition_number: usize,
+    ) -> Result<(&V::Header, &[V::Item]), ProgramError> {
+        header_tail_from_bytes::<V>(self.get_bytes_with_repetition::<V>(repetition_number)?)
+    }
+
+    /// Unpack a portion of the TLV data as the header and items of a
+    /// `PodHeaderTail` type for the first entry found, without copying
+    fn get_first_header_tail<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &self,
+    ) -> Result<(&V::Header, &[V::Item]), ProgramError> {
+        self.get_header_tail_with_repetition::<V>(0)
+    }
+
+    /// Counts the number of entries for the given type in a single pass
+    fn count<V: SplDiscriminate>(&self) -> Result<usize, ProgramError> {
+        self.iter().try_fold(0, |count, entry| {
+            let (discriminator, ..) = entry?;
+            Ok(if discriminator == V::SPL_DISCRIMINATOR {
+                count + 1
+            } else {
+                count
+            })
+        })
+    }
+
+    /// Unpack the TLV data as bytes for every entry of the given type, in
+    /// repetition order, in a single pass
+    fn get_all_bytes<V: SplDiscriminate>(&self) -> Result<Vec<&[u8]>, ProgramError> {
+        self.iter()
+            .filter_map(|entry| match entry {
+                Ok((discriminator, _, _, value)) => {
+                    (discriminator == V::SPL_DISCRIMINATOR).then_some(Ok(value))
+                }
+                Err(e) => Some(Err(e)),
+            })
+            .collect()
+    }
+
+    /// Unpack the TLV data as the desired Pod type for every entry of the
+    /// given type, in repetition order, in a single pass
+    fn get_all_values<V: SplDiscriminate + Pod>(&self) -> Result<Vec<&V>, ProgramError> {
+        self.get_all_bytes::<V>()?
+            .into_iter()
+            .map(pod_from_bytes::<V>)
+            .collect()
+    }
+
+    /// Unpacks the TLV data as the desired variable-length type for every
+    /// entry of the given type, in repetition order, in a single pass
+    fn get_all_variable_len_values<V: SplDiscriminate + VariableLenPack>(
+        &self,
+    ) -> Result<Vec<V>, ProgramError> {
+        self.get_all_bytes::<V>()?
+            .into_iter()
+            .map(V::unpack_from_slice)
+            .collect()
+    }
+
+    /// Unpack the value for the entry number specified as a nested TLV
+    /// container, using the same layout as this one
+    fn get_nested_with_repetition<V: SplDiscriminate>(
+        &self,
+        repetition_number: usize,
+    ) -> Result<TlvStateBorrowed<'_>, ProgramError> {
+        let data = self.get_bytes_with_repetition::<V>(repetition_number)?;
+        TlvStateBorrowed::unpack_with_layout(data, self.get_layout())
+    }
+
+    /// Unpack the value for the first entry found as a nested TLV container,
+    /// using the same layout as this one
+    fn get_nested<V: SplDiscriminate>(&self) -> Result<TlvStateBorrowed<'_>, ProgramError> {
+        self.get_nested_with_repetition::<V>(0)
+    }
+
     /// Iterates through the TLV entries, returning only the types
     fn get_discriminators(&self) -> Result<Vec<ArrayDiscriminator>, ProgramError> {
-        get_discriminators_and_end_index(self.get_data()).map(|v| v.0)
+        self.iter()
+            .map(|entry| entry.map(|(discriminator, ..)| discriminator))
+            .collect()
+    }
+
+    /// Lazily iterates through the TLV entries, yielding the discriminator,
+    /// repetition number, value start index, and value bytes of each one
+    fn iter(&self) -> TlvIter<'_> {
+        TlvIter::new(self.get_layout(), self.get_data())
+    }
+
+    /// Walks through all of the TLV data, reporting every structural problem
+    /// found along with its offset, including any non-zero data after the
+    /// end of the entries.
+    ///
+    /// After a malformed entry, the walk resumes at the next offset, on the
+    /// layout's value alignment, where a complete entry can be read. The real
+    /// start of the next entry can't be known, so the problems reported after
+    /// the first one are a best effort.
+    ///
+    /// This is stricter than unpacking, and is meant for debugging corrupted
+    /// data off-chain.
+    fn validate(&self) -> Result<(), Vec<TlvDataError>> {
+        let layout = self.get_layout();
+        let tlv_data = self.get_data();
+        let mut errors = vec![];
+        let mut start_index = 0;
+        while start_index < tlv_data.len() {
+            match get_entry_bounds(&layout, tlv_data, start_index) {
+                Ok(Some((_, _, _, entry_end))) => start_index = entry_end,
+                Ok(None) => {
+                    if let Some(position) = tlv_data[start_index..].iter().position(|&x| x != 0) {
+                        errors.push(TlvDataError::TrailingData {
+                            offset: start_index.saturating_add(position),
+                        });
+                    }
+                    break;
+                }
+                Err(e) => {
+                    errors.push(e);
+                    // resync on the next entry that can be read
+                    let next_index = (start_index.saturating_add(layout.value_alignment())
+                        ..tlv_data.len())
+                        .step_by(layout.value_alignment())
+                        .find(|&index| {
+                            matches!(get_entry_bounds(&layout, tlv_data, index), Ok(Some(_)))
+                        });
+                    match next_index {
+                        Some(next_index) => start_index = next_index,
+                        None => break,
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Renders every entry on its own line, with the offset of the entry, the
+    /// length of the value, and the type name and decoded value from the
+    /// registry. Unknown types, or values that can't be decoded, are shown
+    /// in hex.
+    fn dump(&self, registry: &TlvRegistry) -> Result<String, ProgramError> {
+        let base_len = self.get_layout().base_len();
+        let mut output = String::new();
+        for entry in self.iter() {
+            let (discriminator, repetition_number, value_start, value) = entry?;
+            let name = registry
+                .get_name(&discriminator)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("unknown {}", to_hex(discriminator.as_slice())));
+            let decoded = match registry.decode(&discriminator, value) {
+                Some(Ok(decoded)) => decoded,
+                Some(Err(e)) => format!("{} (failed to decode: {})", to_hex(value), e),
+                None => to_hex(value),
+            };
+            output.push_str(&format!(
+                "offset {}, length {}: {} #{} = {}\n",
+                value_start.saturating_sub(base_len),
+                value.len(),
+                name,
+                repetition_number,
+                decoded,
+            ));
+        }
+        Ok(output)
     }
 
-    /// Get the base size required for TLV data
+    /// Get the base size required for TLV data with the default layout
     fn get_base_len() -> usize {
         get_base_len()
     }
@@ -270,20 +640,33 @@ pub trait TlvState {
 pub struct TlvStateOwned {
     /// Raw TLV data, deserialized on demand
     data: Vec<u8>,
+    /// Layout of the type and length of every entry
+    layout: TlvLayout,
 }
 impl TlvStateOwned {
     /// Unpacks TLV state data
     ///
     /// Fails if no state is initialized or if data is too small
     pub fn unpack(data: Vec<u8>) -> Result<Self, ProgramError> {
-        check_data(&data)?;
-        Ok(Self { data })
+        Self::unpack_with_layout(data, TlvLayout::DEFAULT)
+    }
+
+    /// Unpacks TLV state data using the given layout
+    ///
+    /// Fails if no state is initialized or if data is too small
+    pub fn unpack_with_layout(data: Vec<u8>, layout: TlvLayout) -> Result<Self, ProgramError> {
+        check_data(&layout, &data)?;
+        Ok(Self { data, layout })
     }
 }
 impl TlvState for TlvStateOwned {
     fn get_data(&self) -> &[u8] {
         &self.data
     }
+
+    fn get_layout(&self) -> TlvLayout {
+        self.layout
+    }
 }
 
 /// Encapsulates immutable base state data (mint or account) with possible
@@ -292,20 +675,63 @@ impl TlvState for TlvStateOwned {
 pub struct TlvStateBorrowed<'data> {
     /// Slice of data containing all TLV data, deserialized on demand
     data: &'data [u8],
+    /// Layout of the type and length of every entry
+    layout: TlvLayout,
 }
 impl<'data> TlvStateBorrowed<'data> {
     /// Unpacks TLV state data
     ///
     /// Fails if no state is initialized or if data is too small
     pub fn unpack(data: &'data [u8]) -> Result<Self, ProgramError> {
-        check_data(data)?;
-        Ok(Self { data })
+        Self::unpack_with_layout(data, TlvLayout::DEFAULT)
+    }
+
+    /// Unpacks TLV state data using the given layout
+    ///
+    /// Fails if no state is initialized or if data is too small
+    pub fn unpack_with_layout(data: &'data [u8], layout: TlvLayout) -> Result<Self, ProgramError> {
+        check_data(&layout, data)?;
+        Ok(Self { data, layout })
+    }
+
+    /// Unpacks TLV state data, checking that the last entry is a
+    /// `TlvChecksum` matching all of the other entries, and that there's no
+    /// data after it
+    pub fn unpack_verified(data: &'data [u8]) -> Result<Self, ProgramError> {
+        Self::unpack_verified_with_layout(data, TlvLayout::DEFAULT)
+    }
+
+    /// Unpacks TLV state data using the given layout, checking the checksum
+    /// like `unpack_verified`
+    pub fn unpack_verified_with_layout(
+        data: &'data [u8],
+        layout: TlvLayout,
+    ) -> Result<Self, ProgramError> {
+        let entries = get_all_entry_bounds(&layout, data)?;
+        let (_, type_start, value_end, entry_end) = entries
+            .iter()
+            .rev()
+            .find(|(discriminator, ..)| *discriminator == TlvChecksum::SPL_DISCRIMINATOR)
+            .ok_or(TlvError::TypeNotFound)?;
+        let value_start = type_start.saturating_add(layout.base_len());
+        let checksum = pod_from_bytes::<TlvChecksum>(&data[value_start..*value_end])?;
+        if *checksum != TlvChecksum::compute(&data[..*type_start]) {
+            return Err(TlvError::ChecksumMismatch.into());
+        }
+        if data[*entry_end..].iter().any(|&x| x != 0) {
+            return Err(TlvError::TrailingData.into());
+        }
+        Ok(Self { data, layout })
     }
 }
 impl TlvState for TlvStateBorrowed<'_> {
     fn get_data(&self) -> &[u8] {
         self.data
     }
+
+    fn get_layout(&self) -> TlvLayout {
+        self.layout
+    }
 }
 
 /// Encapsulates mutable base state data (mint or account) with possible
@@ -314,14 +740,129 @@ impl TlvState for TlvStateBorrowed<'_> {
 pub struct TlvStateMut<'data> {
     /// Slice of data containing all TLV data, deserialized on demand
     data: &'data mut [u8],
+    /// Layout of the type and length of every entry
+    layout: TlvLayout,
+    /// Whether to keep a checksum entry up to date at the end of the data
+    checksum: bool,
 }
 impl<'data> TlvStateMut<'data> {
     /// Unpacks TLV state data
     ///
     /// Fails if no state is initialized or if data is too small
     pub fn unpack(data: &'data mut [u8]) -> Result<Self, ProgramError> {
-        check_data(data)?;
-        Ok(Self { data })
+        Self::unpack_with_layout(data, TlvLayout::DEFAULT)
+    }
+
+    /// Unpacks TLV state data using the given layout
+    ///
+    /// Fails if no state is initialized or if data is too small
+    pub fn unpack_with_layout(
+        data: &'data mut [u8],
+        layout: TlvLayout,
+    ) -> Result<Self, ProgramError> {
+        check_data(&layout, data)?;
+        Ok(Self {
+            data,
+            layout,
+            checksum: false,
+        })
+    }
+
+    /// Keeps a `TlvChecksum` entry over all other entries at the end of the
+    /// data, updating it on every allocation, reallocation, removal, or pack.
+    ///
+    /// Values modified through mutable references or returned slices aren't
+    /// tracked, so call `update_checksum` after writing them. The data must
+    /// have room for the checksum entry, and the layout must fit its
+    /// discriminator.
+    ///
+    /// The account realloc helpers, such as `alloc_with_account_realloc`, do
+    /// this on their own when the last entry of the account is a checksum.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = true;
+        self
+    }
+
+    /// Writes the `TlvChecksum` entry at the end of the data, over all other
+    /// entries, replacing any previous checksum entry at the end
+    pub fn update_checksum(&mut self) -> Result<(), ProgramError> {
+        let (checksum_range, end_index) = self.find_checksum()?;
+        // check before removing the previous checksum, to leave the data as is
+        if self.data.len() < end_index.saturating_add(self.checksum_entry_len()) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if let Some(range) = checksum_range {
+            self.data[range].fill(0);
+        }
+        let checksum = TlvChecksum::compute(&self.data[..end_index]);
+        // other checksum entries may come before, only the last one matters
+        let (range, _) = self.alloc_range::<TlvChecksum>(size_of::<TlvChecksum>(), true)?;
+        self.data[range].copy_from_slice(pod_bytes_of(&checksum));
+        Ok(())
+    }
+
+    /// Size of the whole `TlvChecksum` entry with this layout
+    fn checksum_entry_len(&self) -> usize {
+        self.layout.entry_len(size_of::<TlvChecksum>())
+    }
+
+    /// Finds the range of the `TlvChecksum` entry, if it's the last one, along
+    /// with the end of all other entries
+    fn find_checksum(&self) -> Result<(Option<Range<usize>>, usize), ProgramError> {
+        let entries = get_all_entry_bounds(&self.layout, self.data)?;
+        Ok(match entries.last() {
+            Some(&(discriminator, type_start, _, entry_end))
+                if discriminator == TlvChecksum::SPL_DISCRIMINATOR =>
+            {
+                (Some(type_start..entry_end), type_start)
+            }
+            Some(&(.., entry_end)) => (None, entry_end),
+            None => (None, 0),
+        })
+    }
+
+    /// Runs a change to the entries with the checksum entry taken out, then
+    /// writes it again, if the checksum is enabled.
+    ///
+    /// The change only sees the data up to the space needed by the checksum
+    /// entry, so it can never take that space, and nothing is modified if
+    /// there's no room for the checksum to begin with.
+    fn with_checksum_removed<T>(
+        &mut self,
+        f: impl FnOnce(&mut TlvStateMut<'_>) -> Result<T, ProgramError>,
+    ) -> Result<T, ProgramError> {
+        if !self.checksum {
+            return f(self);
+        }
+        let (checksum_range, end_index) = self.find_checksum()?;
+        let available_len = self
+            .data
+            .len()
+            .checked_sub(self.checksum_entry_len())
+            .filter(|available_len| *available_len >= end_index)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if let Some(range) = checksum_range {
+            self.data[range].fill(0);
+        }
+        let result = f(&mut TlvStateMut {
+            data: &mut self.data[..available_len],
+            layout: self.layout,
+            checksum: false,
+        });
+        self.update_checksum()?;
+        result
+    }
+
+    /// Get the full mutable buffer containing all TLV data
+    pub(crate) fn get_data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// Lazily iterates through the TLV entries, yielding the discriminator,
+    /// repetition number, value start index, and mutable value bytes of each
+    /// one
+    pub fn iter_mut(&mut self) -> TlvIterMut<'_> {
+        TlvIterMut::new(self.layout, self.data)
     }
 
     /// Unpack a portion of the TLV data as the desired type that allows
@@ -344,7 +885,7 @@ impl<'data> TlvStateMut<'data> {
 
     /// Unpack a portion of the TLV data as mutable bytes for the entry number
     /// specified
-    pub fn get_bytes_with_repetition_mut<V: SplDiscriminate>(
+    pub fn get_bytes_with_repetition_mut<V: SplDiscriminate + ?Sized>(
         &mut self,
         repetition_number: usize,
     ) -> Result<&mut [u8], ProgramError> {
@@ -354,14 +895,18 @@ impl<'data> TlvStateMut<'data> {
             value_start,
             value_repetition_number: _,
         } = get_indices(
+            &self.layout,
             self.data,
             V::SPL_DISCRIMINATOR,
             false,
             Some(repetition_number),
         )?;
 
-        let length = pod_from_bytes::<Length>(&self.data[length_start..value_start])?;
-        let value_end = value_start.saturating_add(usize::try_from(*length)?);
+        let length = self
+            .layout
+            .length_size()
+            .read(&self.data[length_start..value_start])?;
+        let value_end = value_start.saturating_add(length);
         if self.data.len() < value_end {
             return Err(ProgramError::InvalidAccountData);
         }
@@ -374,6 +919,87 @@ impl<'data> TlvStateMut<'data> {
         self.get_bytes_with_repetition_mut::<V>(0)
     }
 
+    /// Unpack a portion of the TLV data as the mutable header and items of a
+    /// `PodHeaderTail` type for the entry number specified
+    pub fn get_header_tail_with_repetition_mut<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<(&mut V::Header, &mut [V::Item]), ProgramError> {
+        header_tail_from_bytes_mut::<V>(self.get_bytes_with_repetition_mut::<V>(repetition_number)?)
+    }
+
+    /// Unpack a portion of the TLV data as the mutable header and items of a
+    /// `PodHeaderTail` type for the first entry found
+    pub fn get_first_header_tail_mut<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &mut self,
+    ) -> Result<(&mut V::Header, &mut [V::Item]), ProgramError> {
+        self.get_header_tail_with_repetition_mut::<V>(0)
+    }
+
+    /// Unpack the given entries as mutable bytes at the same time. Each
+    /// request is a discriminator and repetition number, and the result has
+    /// the same order as the requests.
+    ///
+    /// Fails if any entry is not found, or if the same entry is requested more
+    /// than once.
+    pub fn get_many_bytes_mut(
+        &mut self,
+        requests: &[(ArrayDiscriminator, usize)],
+    ) -> Result<Vec<&mut [u8]>, ProgramError> {
+        for (i, request) in requests.iter().enumerate() {
+            if requests[..i].contains(request) {
+                return Err(TlvError::DuplicateEntry.into());
+            }
+        }
+        let mut found: Vec<Option<&mut [u8]>> = requests.iter().map(|_| None).collect();
+        for entry in self.iter_mut() {
+            let (discriminator, repetition_number, _, value) = entry?;
+            if let Some(i) = requests
+                .iter()
+                .position(|request| *request == (discriminator, repetition_number))
+            {
+                found[i] = Some(value);
+            }
+        }
+        found
+            .into_iter()
+            .map(|value| value.ok_or_else(|| TlvError::TypeNotFound.into()))
+            .collect()
+    }
+
+    /// Unpack the first entry of every type in the tuple as mutable references
+    /// at the same time, for example `get_many_mut::<(A, B, C)>()`.
+    ///
+    /// Fails if any type is not found, or if two types have the same
+    /// discriminator.
+    pub fn get_many_mut<T: TlvEntriesMut>(&mut self) -> Result<T::Output<'_>, ProgramError> {
+        let discriminators = T::discriminators();
+        self.get_many_mut_with_repetition::<T>(&vec![0; discriminators.len()])
+    }
+
+    /// Unpack the entries of every type in the tuple, with the repetition
+    /// number given for each one, as mutable references at the same time.
+    /// The same type can be used more than once with different repetition
+    /// numbers, for example `get_many_mut_with_repetition::<(A, A)>(&[0, 1])`.
+    ///
+    /// Fails if any entry is not found, or if the same entry is requested more
+    /// than once.
+    pub fn get_many_mut_with_repetition<T: TlvEntriesMut>(
+        &mut self,
+        repetition_numbers: &[usize],
+    ) -> Result<T::Output<'_>, ProgramError> {
+        let discriminators = T::discriminators();
+        if discriminators.len() != repetition_numbers.len() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let requests = discriminators
+            .into_iter()
+            .zip(repetition_numbers.iter().copied())
+            .collect::<Vec<_>>();
+        let values = self.get_many_bytes_mut(&requests)?;
+        T::from_bytes_mut(values)
+    }
+
     /// Packs the default TLV data into the first open slot in the data buffer.
     /// Handles repetition based on the Boolean arg provided:
     /// * `true`:   If extension is already found in the buffer, it returns an
@@ -384,9 +1010,12 @@ impl<'data> TlvStateMut<'data> {
         allow_repetition: bool,
     ) -> Result<(&mut V, usize), ProgramError> {
         let length = size_of::<V>();
-        let (buffer, repetition_number) = self.alloc::<V>(length, allow_repetition)?;
-        let extension_ref = pod_from_bytes_mut::<V>(buffer)?;
-        *extension_ref = V::default();
+        let (range, repetition_number) = self.with_checksum_removed(|state| {
+            let (range, repetition_number) = state.alloc_range::<V>(length, allow_repetition)?;
+            *pod_from_bytes_mut::<V>(&mut state.data[range.clone()])? = V::default();
+            Ok((range, repetition_number))
+        })?;
+        let extension_ref = pod_from_bytes_mut::<V>(&mut self.data[range])?;
         Ok((extension_ref, repetition_number))
     }
 
@@ -397,10 +1026,12 @@ impl<'data> TlvStateMut<'data> {
         value: &V,
         repetition_number: usize,
     ) -> Result<(), ProgramError> {
-        let data = self.get_bytes_with_repetition_mut::<V>(repetition_number)?;
-        // NOTE: Do *not* use `pack`, since the length check will cause
-        // reallocations to smaller sizes to fail
-        value.pack_into_slice(data)
+        self.with_checksum_removed(|state| {
+            let data = state.get_bytes_with_repetition_mut::<V>(repetition_number)?;
+            // NOTE: Do *not* use `pack`, since the length check will cause
+            // reallocations to smaller sizes to fail
+            value.pack_into_slice(data)
+        })
     }
 
     /// Packs a variable-length value into its appropriate data segment, where
@@ -412,42 +1043,63 @@ impl<'data> TlvStateMut<'data> {
         self.pack_variable_len_value_with_repetition::<V>(value, 0)
     }
 
-    /// Allocate the given number of bytes for the given `SplDiscriminate`
-    pub fn alloc<V: SplDiscriminate>(
+    /// Allocate the given number of bytes for the given `SplDiscriminate`.
+    ///
+    /// If the checksum is enabled, it covers the zeroed value, so call
+    /// `update_checksum` after writing to the returned slice.
+    pub fn alloc<V: SplDiscriminate + ?Sized>(
         &mut self,
         length: usize,
         allow_repetition: bool,
     ) -> Result<(&mut [u8], usize), ProgramError> {
+        let (range, repetition_number) =
+            self.with_checksum_removed(|state| state.alloc_range::<V>(length, allow_repetition))?;
+        Ok((&mut self.data[range], repetition_number))
+    }
+
+    /// Allocate the given number of bytes for the given `SplDiscriminate`,
+    /// returning the range of the value
+    fn alloc_range<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        allow_repetition: bool,
+    ) -> Result<(Range<usize>, usize), ProgramError> {
         let TlvIndices {
             type_start,
             length_start,
             value_start,
             value_repetition_number,
         } = get_indices(
+            &self.layout,
             self.data,
             V::SPL_DISCRIMINATOR,
             true,
             if allow_repetition { None } else { Some(0) },
         )?;
 
-        let discriminator = ArrayDiscriminator::try_from(&self.data[type_start..length_start])?;
+        let discriminator = self
+            .layout
+            .read_discriminator(&self.data[type_start..length_start])?;
         if discriminator == ArrayDiscriminator::UNINITIALIZED {
-            // write type
-            let discriminator_ref = &mut self.data[type_start..length_start];
-            discriminator_ref.copy_from_slice(V::SPL_DISCRIMINATOR.as_ref());
+            // check before writing anything, to leave the data as is on failure
+            if !self.layout.fits(V::SPL_DISCRIMINATOR) {
+                return Err(TlvError::DiscriminatorTooLarge.into());
+            }
+            if self.data.len() < value_start.saturating_add(self.layout.padded_len(length)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
             // write length
-            let length_ref =
-                pod_from_bytes_mut::<Length>(&mut self.data[length_start..value_start])?;
-            *length_ref = Length::try_from(length)?;
+            self.layout
+                .length_size()
+                .write(length, &mut self.data[length_start..value_start])?;
+            // write type
+            self.layout.write_discriminator(
+                V::SPL_DISCRIMINATOR,
+                &mut self.data[type_start..length_start],
+            )?;
 
             let value_end = value_start.saturating_add(length);
-            if self.data.len() < value_end {
-                return Err(ProgramError::InvalidAccountData);
-            }
-            Ok((
-                &mut self.data[value_start..value_end],
-                value_repetition_number,
-            ))
+            Ok((value_start..value_end, value_repetition_number))
         } else {
             Err(TlvError::TypeAlreadyExists.into())
         }
@@ -460,40 +1112,115 @@ impl<'data> TlvStateMut<'data> {
         allow_repetition: bool,
     ) -> Result<usize, ProgramError> {
         let length = value.get_packed_len()?;
-        let (data, repetition_number) = self.alloc::<V>(length, allow_repetition)?;
-        value.pack_into_slice(data)?;
-        Ok(repetition_number)
+        self.with_checksum_removed(|state| {
+            let (range, repetition_number) = state.alloc_range::<V>(length, allow_repetition)?;
+            value.pack_into_slice(&mut state.data[range])?;
+            Ok(repetition_number)
+        })
+    }
+
+    /// Allocates and writes a new TLV entry for a `PodHeaderTail` type, with
+    /// the given header and items
+    pub fn alloc_and_pack_header_tail_entry<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &mut self,
+        header: &V::Header,
+        items: &[V::Item],
+        allow_repetition: bool,
+    ) -> Result<usize, ProgramError> {
+        let header_bytes = pod_bytes_of(header);
+        let items_bytes = pod_slice_to_bytes(items);
+        self.with_checksum_removed(|state| {
+            let (range, repetition_number) = state.alloc_range::<V>(
+                header_bytes.len().saturating_add(items_bytes.len()),
+                allow_repetition,
+            )?;
+            let (header_data, items_data) = state.data[range].split_at_mut(header_bytes.len());
+            header_data.copy_from_slice(header_bytes);
+            items_data.copy_from_slice(items_bytes);
+            Ok(repetition_number)
+        })
+    }
+
+    /// Appends an item to the tail of a `PodHeaderTail` entry, reallocating
+    /// the entry to make room for it
+    pub fn push_header_tail_item_with_repetition<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &mut self,
+        item: &V::Item,
+        repetition_number: usize,
+    ) -> Result<(), ProgramError> {
+        let item_bytes = pod_bytes_of(item);
+        let old_length = {
+            let value = self.get_bytes_with_repetition::<V>(repetition_number)?;
+            // validate the current value before growing it
+            let _ = header_tail_from_bytes::<V>(value)?;
+            value.len()
+        };
+        self.with_checksum_removed(|state| {
+            let range = state.realloc_range::<V>(
+                old_length.saturating_add(item_bytes.len()),
+                repetition_number,
+            )?;
+            state.data[range][old_length..].copy_from_slice(item_bytes);
+            Ok(())
+        })
+    }
+
+    /// Appends an item to the tail of the first `PodHeaderTail` entry found
+    pub fn push_first_header_tail_item<V: SplDiscriminate + PodHeaderTail + ?Sized>(
+        &mut self,
+        item: &V::Item,
+    ) -> Result<(), ProgramError> {
+        self.push_header_tail_item_with_repetition::<V>(item, 0)
     }
 
     /// Reallocate the given number of bytes for the given `SplDiscriminate`. If
     /// the new length is smaller, it will compact the rest of the buffer
     /// and zero out the difference at the end. If it's larger, it will move
     /// the rest of the buffer data and zero out the new data.
-    pub fn realloc_with_repetition<V: SplDiscriminate>(
+    ///
+    /// If the checksum is enabled, it covers the value as reallocated, so
+    /// call `update_checksum` after writing to the returned slice.
+    pub fn realloc_with_repetition<V: SplDiscriminate + ?Sized>(
         &mut self,
         length: usize,
         repetition_number: usize,
     ) -> Result<&mut [u8], ProgramError> {
+        let range = self
+            .with_checksum_removed(|state| state.realloc_range::<V>(length, repetition_number))?;
+        Ok(&mut self.data[range])
+    }
+
+    /// Reallocate the given number of bytes for the given `SplDiscriminate`,
+    /// returning the new range of the value
+    fn realloc_range<V: SplDiscriminate + ?Sized>(
+        &mut self,
+        length: usize,
+        repetition_number: usize,
+    ) -> Result<Range<usize>, ProgramError> {
         let TlvIndices {
             type_start: _,
             length_start,
             value_start,
             value_repetition_number: _,
         } = get_indices(
+            &self.layout,
             self.data,
             V::SPL_DISCRIMINATOR,
             false,
             Some(repetition_number),
         )?;
-        let (_, end_index) = get_discriminators_and_end_index(self.data)?;
+        let (_, end_index) = get_discriminators_and_end_index(&self.layout, self.data)?;
         let data_len = self.data.len();
 
-        let length_ref = pod_from_bytes_mut::<Length>(&mut self.data[length_start..value_start])?;
-        let old_length = usize::try_from(*length_ref)?;
+        let length_size = self.layout.length_size();
+        let old_length = length_size.read(&self.data[length_start..value_start])?;
+        let old_padded_length = self.layout.padded_len(old_length);
+        let new_padded_length = self.layout.padded_len(length);
 
         // check that we're not going to panic during `copy_within`
-        if old_length < length {
-            let new_end_index = end_index.saturating_add(length.saturating_sub(old_length));
+        if old_padded_length < new_padded_length {
+            let new_end_index =
+                end_index.saturating_add(new_padded_length.saturating_sub(old_padded_length));
             if new_end_index > data_len {
                 return Err(ProgramError::InvalidAccountData);
             }
@@ -501,86 +1228,501 @@ impl<'data> TlvStateMut<'data> {
 
         // write new length after the check, to avoid getting into a bad situation
         // if trying to recover from an error
-        *length_ref = Length::try_from(length)?;
+        length_size.write(length, &mut self.data[length_start..value_start])?;
 
         let old_value_end = value_start.saturating_add(old_length);
         let new_value_end = value_start.saturating_add(length);
+        let old_entry_end = value_start.saturating_add(old_padded_length);
+        let new_entry_end = value_start.saturating_add(new_padded_length);
         self.data
-            .copy_within(old_value_end..end_index, new_value_end);
+            .copy_within(old_entry_end..end_index, new_entry_end);
         match old_length.cmp(&length) {
             Ordering::Greater => {
-                // realloc to smaller, fill the end
-                let new_end_index = end_index.saturating_sub(old_length.saturating_sub(length));
+                // realloc to smaller, fill the end and the new padding
+                let new_end_index =
+                    end_index.saturating_sub(old_padded_length.saturating_sub(new_padded_length));
                 self.data[new_end_index..end_index].fill(0);
+                self.data[new_value_end..new_entry_end].fill(0);
             }
             Ordering::Less => {
                 // realloc to bigger, fill the moved part
-                self.data[old_value_end..new_value_end].fill(0);
+                self.data[old_value_end..new_entry_end].fill(0);
             }
             Ordering::Equal => {} // nothing needed!
         }
 
-        Ok(&mut self.data[value_start..new_value_end])
+        Ok(value_start..new_value_end)
     }
 
     /// Reallocate the given number of bytes for the given `SplDiscriminate`,
-    /// where no repeating discriminators are allowed
+    /// where no repeating discriminators are allowed. See
+    /// `realloc_with_repetition` for keeping the checksum up to date.
     pub fn realloc_first<V: SplDiscriminate>(
         &mut self,
         length: usize,
     ) -> Result<&mut [u8], ProgramError> {
         self.realloc_with_repetition::<V>(length, 0)
     }
-}
 
-impl TlvState for TlvStateMut<'_> {
-    fn get_data(&self) -> &[u8] {
-        self.data
+    /// Remove the entry for the given `SplDiscriminate` and repetition number.
+    /// All following entries are shifted down to fill the gap, and the freed
+    /// space at the end of the buffer is zeroed out. Note that any later
+    /// entries with the same discriminator will have their repetition number
+    /// decreased by one.
+    ///
+    /// Returns the number of bytes freed.
+    pub fn remove<V: SplDiscriminate>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<usize, ProgramError> {
+        self.remove_by_discriminator(V::SPL_DISCRIMINATOR, repetition_number)
     }
-}
 
-/// Packs a variable-length value into an existing TLV space, reallocating
-/// the account and TLV as needed to accommodate for any change in space
-pub fn realloc_and_pack_variable_len_with_repetition<V: SplDiscriminate + VariableLenPack>(
-    account_info: &AccountInfo,
-    value: &V,
-    repetition_number: usize,
-) -> Result<(), ProgramError> {
-    let previous_length = {
-        let data = account_info.try_borrow_data()?;
-        let TlvIndices {
-            type_start: _,
-            length_start,
-            value_start,
-            value_repetition_number: _,
-        } = get_indices(&data, V::SPL_DISCRIMINATOR, false, Some(repetition_number))?;
-        usize::try_from(*pod_from_bytes::<Length>(&data[length_start..value_start])?)?
-    };
-    let new_length = value.get_packed_len()?;
-    let previous_account_size = account_info.try_data_len()?;
+    /// Remove the first entry for the given `SplDiscriminate`, where no
+    /// repeating discriminators are allowed
+    pub fn remove_first<V: SplDiscriminate>(&mut self) -> Result<usize, ProgramError> {
+        self.remove::<V>(0)
+    }
+
+    /// Remove the entry for the given discriminator and repetition number,
+    /// compacting the rest of the buffer. See `remove` for more information.
+    pub fn remove_by_discriminator(
+        &mut self,
+        discriminator: ArrayDiscriminator,
+        repetition_number: usize,
+    ) -> Result<usize, ProgramError> {
+        self.with_checksum_removed(|state| state.remove_entry(discriminator, repetition_number))
+    }
+
+    /// Remove the entry for the given discriminator and repetition number,
+    /// returning the number of bytes freed
+    fn remove_entry(
+        &mut self,
+        discriminator: ArrayDiscriminator,
+        repetition_number: usize,
+    ) -> Result<usize, ProgramError> {
+        let TlvIndices {
+            type_start,
+            length_start,
+            value_start,
+            value_repetition_number: _,
+        } = get_indices(
+            &self.layout,
+            self.data,
+            discriminator,
+            false,
+            Some(repetition_number),
+        )?;
+        let (_, end_index) = get_discriminators_and_end_index(&self.layout, self.data)?;
+
+        let length = self
+            .layout
+            .length_size()
+            .read(&self.data[length_start..value_start])?;
+        let entry_end = value_start.saturating_add(self.layout.padded_len(length));
+        let removed_bytes = entry_end.saturating_sub(type_start);
+
+        self.data.copy_within(entry_end..end_index, type_start);
+        let new_end_index = end_index.saturating_sub(removed_bytes);
+        self.data[new_end_index..end_index].fill(0);
+
+        Ok(removed_bytes)
+    }
+
+    /// Sorts the entries by discriminator, keeping entries with the same
+    /// discriminator in repetition order, so that buffers with the same
+    /// entries have the same bytes regardless of insertion order.
+    ///
+    /// Combine with `defragment` to also clear any stale bytes.
+    pub fn canonicalize(&mut self) -> Result<(), ProgramError> {
+        self.with_checksum_removed(|state| {
+            let mut entries = get_all_entry_bounds(&state.layout, state.data)?;
+            // stable sort, so repetitions keep their relative order
+            entries.sort_by_key(|(discriminator, ..)| {
+                <[u8; ArrayDiscriminator::LENGTH]>::from(*discriminator)
+            });
+            let end_index = entries.iter().map(|(_, _, _, end)| *end).max().unwrap_or(0);
+            let mut sorted = Vec::with_capacity(end_index);
+            for (_, start, _, end) in entries {
+                sorted.extend_from_slice(&state.data[start..end]);
+            }
+            state.data[..end_index].copy_from_slice(&sorted);
+            Ok(())
+        })
+    }
+
+    /// Zeroes out any stale bytes left in the buffer: the padding after every
+    /// value, and all of the free space after the last entry. Entries are
+    /// always contiguous, so all free space is already at the end of the
+    /// buffer, and entries keep their order.
+    ///
+    /// Returns the number of free bytes at the end of the buffer.
+    pub fn defragment(&mut self) -> Result<usize, ProgramError> {
+        self.with_checksum_removed(|state| {
+            let entries = get_all_entry_bounds(&state.layout, state.data)?;
+            let mut end_index = 0;
+            for (_, _, value_end, entry_end) in entries {
+                state.data[value_end..entry_end].fill(0);
+                end_index = entry_end;
+            }
+            state.data[end_index..].fill(0);
+            Ok(())
+        })?;
+        let (_, end_index) = get_discriminators_and_end_index(&self.layout, self.data)?;
+        // the space kept for the checksum entry is only cleared here
+        self.data[end_index..].fill(0);
+        Ok(self.data.len().saturating_sub(end_index))
+    }
+
+    /// Upgrades every entry of the previous version of `V` in place, writing
+    /// the new discriminator and migrated value, and reallocating the entry if
+    /// the size changes. Entries keep their position in the buffer.
+    ///
+    /// If `V` shares its discriminator with the previous version, only
+    /// entries whose version byte is lower than `V::VERSION` are upgraded.
+    ///
+    /// Returns the number of entries migrated.
+    pub fn migrate_all<V: TlvMigrate>(&mut self) -> Result<usize, ProgramError> {
+        // check up front, to avoid leaving a reallocated entry behind
+        if !self.layout.fits(V::SPL_DISCRIMINATOR) {
+            return Err(TlvError::DiscriminatorTooLarge.into());
+        }
+        let shared_discriminator =
+            V::SPL_DISCRIMINATOR == <V::Previous as SplDiscriminate>::SPL_DISCRIMINATOR;
+        if shared_discriminator && V::VERSION.is_none() {
+            return Err(TlvError::MissingVersion.into());
+        }
+        self.with_checksum_removed(|state| {
+            let count = state.count::<V::Previous>()?;
+            let mut repetition_number = 0;
+            let mut migrated = 0;
+            for _ in 0..count {
+                let previous = state
+                    .get_bytes_with_repetition::<V::Previous>(repetition_number)?
+                    .to_vec();
+                if shared_discriminator {
+                    // entries that are already up to date are still found as
+                    // the previous version, so they are told apart by their
+                    // version byte
+                    let is_previous = matches!(
+                        (previous.first(), V::VERSION),
+                        (Some(&byte), Some(version)) if byte < version
+                    );
+                    if !is_previous {
+                        repetition_number += 1;
+                        continue;
+                    }
+                }
+                let length = V::get_migrated_len(&previous)?;
+                state.realloc_with_repetition::<V::Previous>(length, repetition_number)?;
+                let value_range = get_value_range(
+                    &state.layout,
+                    state.data,
+                    <V::Previous as SplDiscriminate>::SPL_DISCRIMINATOR,
+                    repetition_number,
+                )?;
+                let type_start = value_range.start.saturating_sub(state.layout.base_len());
+                let length_start = type_start.saturating_add(state.layout.discriminator_size());
+                state.layout.write_discriminator(
+                    V::SPL_DISCRIMINATOR,
+                    &mut state.data[type_start..length_start],
+                )?;
+                let value = &mut state.data[value_range];
+                V::migrate(&previous, value)?;
+                if let Some(version) = V::VERSION {
+                    *value.first_mut().ok_or(ProgramError::InvalidAccountData)? = version;
+                }
+                migrated += 1;
+                // otherwise, once migrated, an entry is no longer the previous
+                // version, so the next one to migrate has the same repetition
+                // number
+                if shared_discriminator {
+                    repetition_number += 1;
+                }
+            }
+            Ok(migrated)
+        })
+    }
+
+    /// Unpack the value for the entry number specified as a mutable nested
+    /// TLV container, using the same layout as this one.
+    ///
+    /// The nested container can only use the space already in the value. To
+    /// also resize the outer entry, use `alloc_nested`,
+    /// `realloc_nested_with_repetition`, or `remove_nested`.
+    pub fn get_nested_mut_with_repetition<V: SplDiscriminate>(
+        &mut self,
+        repetition_number: usize,
+    ) -> Result<TlvStateMut<'_>, ProgramError> {
+        let layout = self.layout;
+        let data = self.get_bytes_with_repetition_mut::<V>(repetition_number)?;
+        TlvStateMut::unpack_with_layout(data, layout)
+    }
+
+    /// Unpack the value for the first entry found as a mutable nested TLV
+    /// container, using the same layout as this one
+    pub fn get_nested_mut<V: SplDiscriminate>(&mut self) -> Result<TlvStateMut<'_>, ProgramError> {
+        self.get_nested_mut_with_repetition::<V>(0)
+    }
+
+    /// Allocate the given number of bytes for `I` in the nested container
+    /// held by the `O` entry with the given repetition number, growing the
+    /// outer entry if there isn't enough space in it
+    pub fn alloc_nested<O: SplDiscriminate, I: SplDiscriminate>(
+        &mut self,
+        outer_repetition_number: usize,
+        length: usize,
+        allow_repetition: bool,
+    ) -> Result<(&mut [u8], usize), ProgramError> {
+        let repetition_number = self.with_checksum_removed(|state| {
+            let outer_length;
+            let inner_end_index;
+            {
+                let nested = state.get_nested_mut_with_repetition::<O>(outer_repetition_number)?;
+                if !allow_repetition && nested.get_first_bytes::<I>().is_ok() {
+                    return Err(TlvError::TypeAlreadyExists.into());
+                }
+                outer_length = nested.data.len();
+                (_, inner_end_index) =
+                    get_discriminators_and_end_index(&nested.layout, nested.data)?;
+            }
+            let needed_length = inner_end_index.saturating_add(state.layout.entry_len(length));
+            if needed_length > outer_length {
+                state.realloc_with_repetition::<O>(needed_length, outer_repetition_number)?;
+            }
+            let (_, repetition_number) = state
+                .get_nested_mut_with_repetition::<O>(outer_repetition_number)?
+                .alloc::<I>(length, allow_repetition)?;
+            Ok(repetition_number)
+        })?;
+        let value =
+            self.get_nested_bytes_mut::<O, I>(outer_repetition_number, repetition_number)?;
+        Ok((value, repetition_number))
+    }
+
+    /// Reallocate the given number of bytes for the `I` entry in the nested
+    /// container held by the `O` entry, updating the length of the outer
+    /// entry to match. See `realloc_with_repetition` for more information.
+    pub fn realloc_nested_with_repetition<O: SplDiscriminate, I: SplDiscriminate>(
+        &mut self,
+        outer_repetition_number: usize,
+        inner_repetition_number: usize,
+        length: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        self.with_checksum_removed(|state| {
+            let outer_length;
+            let old_padded_length;
+            let inner_end_index;
+            {
+                let nested = state.get_nested_mut_with_repetition::<O>(outer_repetition_number)?;
+                outer_length = nested.data.len();
+                let old_length = nested
+                    .get_bytes_with_repetition::<I>(inner_repetition_number)?
+                    .len();
+                old_padded_length = nested.layout.padded_len(old_length);
+                (_, inner_end_index) =
+                    get_discriminators_and_end_index(&nested.layout, nested.data)?;
+            }
+            let new_padded_length = state.layout.padded_len(length);
+            if new_padded_length > old_padded_length {
+                // grow the outer entry first, to make room for the inner one
+                let needed_length = inner_end_index
+                    .saturating_add(new_padded_length.saturating_sub(old_padded_length));
+                if needed_length > outer_length {
+                    state.realloc_with_repetition::<O>(needed_length, outer_repetition_number)?;
+                }
+                state
+                    .get_nested_mut_with_repetition::<O>(outer_repetition_number)?
+                    .realloc_with_repetition::<I>(length, inner_repetition_number)?;
+            } else {
+                // shrink the inner entry first, so the outer one only drops zeroes
+                state
+                    .get_nested_mut_with_repetition::<O>(outer_repetition_number)?
+                    .realloc_with_repetition::<I>(length, inner_repetition_number)?;
+                let new_outer_length = outer_length
+                    .saturating_sub(old_padded_length.saturating_sub(new_padded_length));
+                state.realloc_with_repetition::<O>(new_outer_length, outer_repetition_number)?;
+            }
+            Ok(())
+        })?;
+        self.get_nested_bytes_mut::<O, I>(outer_repetition_number, inner_repetition_number)
+    }
+
+    /// Reallocate the given number of bytes for the first `I` entry in the
+    /// nested container held by the first `O` entry
+    pub fn realloc_nested_first<O: SplDiscriminate, I: SplDiscriminate>(
+        &mut self,
+        length: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        self.realloc_nested_with_repetition::<O, I>(0, 0, length)
+    }
+
+    /// Remove the `I` entry from the nested container held by the `O` entry,
+    /// shrinking the outer entry by the same amount.
+    ///
+    /// Returns the number of bytes freed.
+    pub fn remove_nested<O: SplDiscriminate, I: SplDiscriminate>(
+        &mut self,
+        outer_repetition_number: usize,
+        inner_repetition_number: usize,
+    ) -> Result<usize, ProgramError> {
+        self.with_checksum_removed(|state| {
+            let (outer_length, removed_bytes) = {
+                let mut nested =
+                    state.get_nested_mut_with_repetition::<O>(outer_repetition_number)?;
+                let outer_length = nested.data.len();
+                (outer_length, nested.remove::<I>(inner_repetition_number)?)
+            };
+            state.realloc_with_repetition::<O>(
+                outer_length.saturating_sub(removed_bytes),
+                outer_repetition_number,
+            )?;
+            Ok(removed_bytes)
+        })
+    }
+
+    /// Get the value bytes of a nested entry, borrowed from the outer buffer
+    fn get_nested_bytes_mut<O: SplDiscriminate, I: SplDiscriminate>(
+        &mut self,
+        outer_repetition_number: usize,
+        inner_repetition_number: usize,
+    ) -> Result<&mut [u8], ProgramError> {
+        let outer_range = get_value_range(
+            &self.layout,
+            self.data,
+            O::SPL_DISCRIMINATOR,
+            outer_repetition_number,
+        )?;
+        let inner_range = get_value_range(
+            &self.layout,
+            &self.data[outer_range.clone()],
+            I::SPL_DISCRIMINATOR,
+            inner_repetition_number,
+        )?;
+        Ok(
+            &mut self.data[outer_range.start.saturating_add(inner_range.start)
+                ..outer_range.start.saturating_add(inner_range.end)],
+        )
+    }
+}
+
+impl TlvState for TlvStateMut<'_> {
+    fn get_data(&self) -> &[u8] {
+        self.data
+    }
+
+    fn get_layout(&self) -> TlvLayout {
+        self.layout
+    }
+}
+
+/// Tuple of Pod types whose entries can be borrowed mutably at the same time
+/// with `TlvStateMut::get_many_mut`, implemented for tuples of 2 to 5 types
+pub trait TlvEntriesMut {
+    /// Tuple of mutable references to every type
+    type Output<'data>;
+
+    /// Discriminators of every type, in order
+    fn discriminators() -> Vec<ArrayDiscriminator>;
+
+    /// Converts the values for every type, in order, to the output tuple
+    fn from_bytes_mut(values: Vec<&mut [u8]>) -> Result<Self::Output<'_>, ProgramError>;
+}
+
+macro_rules! impl_tlv_entries_mut {
+    ($($name:ident),+) => {
+        impl<$($name: SplDiscriminate + Pod),+> TlvEntriesMut for ($($name,)+) {
+            type Output<'data> = ($(&'data mut $name,)+);
+
+            fn discriminators() -> Vec<ArrayDiscriminator> {
+                vec![$($name::SPL_DISCRIMINATOR),+]
+            }
+
+            fn from_bytes_mut(
+                values: Vec<&mut [u8]>,
+            ) -> Result<Self::Output<'_>, ProgramError> {
+                let mut values = values.into_iter();
+                Ok(($(
+                    pod_from_bytes_mut::<$name>(
+                        values.next().ok_or(ProgramError::InvalidArgument)?,
+                    )?,
+                )+))
+            }
+        }
+    };
+}
+impl_tlv_entries_mut!(A, B);
+impl_tlv_entries_mut!(A, B, C);
+impl_tlv_entries_mut!(A, B, C, D);
+impl_tlv_entries_mut!(A, B, C, D, E);
+
+/// Packs a variable-length value into an existing TLV space, reallocating
+/// the account and TLV as needed to accommodate for any change in space
+pub fn realloc_and_pack_variable_len_with_repetition<V: SplDiscriminate + VariableLenPack>(
+    account_info: &impl TlvAccount,
+    value: &V,
+    repetition_number: usize,
+) -> Result<(), ProgramError> {
+    realloc_and_pack_variable_len_with_repetition_with_layout(
+        account_info,
+        value,
+        repetition_number,
+        TlvLayout::DEFAULT,
+    )
+}
+
+/// Packs a variable-length value into an existing TLV space like
+/// `realloc_and_pack_variable_len_with_repetition`, using the given layout
+pub fn realloc_and_pack_variable_len_with_repetition_with_layout<
+    V: SplDiscriminate + VariableLenPack,
+>(
+    account_info: &impl TlvAccount,
+    value: &V,
+    repetition_number: usize,
+    layout: TlvLayout,
+) -> Result<(), ProgramError> {
+    let previous_length = {
+        let data = account_info.try_borrow_data()?;
+        let TlvIndices {
+            type_start: _,
+            length_start,
+            value_start,
+            value_repetition_number: _,
+        } = get_indices(
+            &layout,
+            &data,
+            V::SPL_DISCRIMINATOR,
+            false,
+            Some(repetition_number),
+        )?;
+        layout
+            .length_size()
+            .read(&data[length_start..value_start])?
+    };
+    let new_length = value.get_packed_len()?;
+    // the account only changes size if the padded length changes
+    let previous_padded_length = layout.padded_len(previous_length);
+    let new_padded_length = layout.padded_len(new_length);
+    let previous_account_size = account_info.try_data_len()?;
     if previous_length < new_length {
         // size increased, so realloc the account, then the TLV entry, then write data
-        let additional_bytes = new_length
-            .checked_sub(previous_length)
-            .ok_or(ProgramError::AccountDataTooSmall)?;
+        let additional_bytes = new_padded_length.saturating_sub(previous_padded_length);
         account_info.realloc(previous_account_size.saturating_add(additional_bytes), true)?;
         let mut buffer = account_info.try_borrow_mut_data()?;
-        let mut state = TlvStateMut::unpack(&mut buffer)?;
+        let mut state = unpack_account_state(&mut buffer, layout)?;
         state.realloc_with_repetition::<V>(new_length, repetition_number)?;
         state.pack_variable_len_value_with_repetition(value, repetition_number)?;
     } else {
         // do it backwards otherwise, write the state, realloc TLV, then the account
         let mut buffer = account_info.try_borrow_mut_data()?;
-        let mut state = TlvStateMut::unpack(&mut buffer)?;
+        let mut state = unpack_account_state(&mut buffer, layout)?;
         state.pack_variable_len_value_with_repetition(value, repetition_number)?;
-        let removed_bytes = previous_length
-            .checked_sub(new_length)
-            .ok_or(ProgramError::AccountDataTooSmall)?;
-        if removed_bytes > 0 {
+        if new_length < previous_length {
             // we decreased the size, so need to realloc the TLV, then the account
             state.realloc_with_repetition::<V>(new_length, repetition_number)?;
             // this is probably fine, but be safe and avoid invalidating references
             drop(buffer);
+            let removed_bytes = previous_padded_length.saturating_sub(new_padded_length);
             account_info.realloc(previous_account_size.saturating_sub(removed_bytes), false)?;
         }
     }
@@ -590,28 +1732,217 @@ pub fn realloc_and_pack_variable_len_with_repetition<V: SplDiscriminate + Variab
 /// Packs a variable-length value into an existing TLV space, where no repeating
 /// discriminators are allowed
 pub fn realloc_and_pack_first_variable_len<V: SplDiscriminate + VariableLenPack>(
-    account_info: &AccountInfo,
+    account_info: &impl TlvAccount,
     value: &V,
 ) -> Result<(), ProgramError> {
     realloc_and_pack_variable_len_with_repetition::<V>(account_info, value, 0)
 }
 
+/// Removes an existing TLV entry, then reallocates the account to release the
+/// freed space. Returns the number of bytes removed from the account.
+pub fn realloc_and_remove_with_repetition<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    repetition_number: usize,
+) -> Result<usize, ProgramError> {
+    realloc_and_remove_with_repetition_with_layout::<V>(
+        account_info,
+        repetition_number,
+        TlvLayout::DEFAULT,
+    )
+}
+
+/// Removes an existing TLV entry like `realloc_and_remove_with_repetition`,
+/// using the given layout
+pub fn realloc_and_remove_with_repetition_with_layout<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    repetition_number: usize,
+    layout: TlvLayout,
+) -> Result<usize, ProgramError> {
+    let removed_bytes = {
+        let mut buffer = account_info.try_borrow_mut_data()?;
+        let mut state = unpack_account_state(&mut buffer, layout)?;
+        state.remove::<V>(repetition_number)?
+    };
+    let previous_account_size = account_info.try_data_len()?;
+    account_info.realloc(previous_account_size.saturating_sub(removed_bytes), false)?;
+    Ok(removed_bytes)
+}
+
+/// Removes an existing TLV entry, where no repeating discriminators are
+/// allowed, then reallocates the account to release the freed space
+pub fn realloc_and_remove_first<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+) -> Result<usize, ProgramError> {
+    realloc_and_remove_with_repetition::<V>(account_info, 0)
+}
+
+/// Account size and rent needed to add a new TLV entry to an account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountAllocSize {
+    /// Size of the account data after adding the entry
+    pub new_account_size: usize,
+    /// Difference between the rent-exempt minimum balance at the new size and
+    /// at the current size
+    pub rent_delta: u64,
+}
+
+/// Calculates the new account size and rent delta for adding an entry with
+/// `alloc_with_account_realloc`, without modifying the account, so that
+/// lamports can be transferred to the account first
+pub fn get_alloc_with_account_realloc_size<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    length: usize,
+    allow_repetition: bool,
+    rent: &Rent,
+) -> Result<AccountAllocSize, ProgramError> {
+    get_alloc_with_account_realloc_size_with_layout::<V>(
+        account_info,
+        length,
+        allow_repetition,
+        rent,
+        TlvLayout::DEFAULT,
+    )
+}
+
+/// Calculates the new account size and rent delta for adding an entry with
+/// `alloc_with_account_realloc_with_layout`, using the given layout
+pub fn get_alloc_with_account_realloc_size_with_layout<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    length: usize,
+    allow_repetition: bool,
+    rent: &Rent,
+    layout: TlvLayout,
+) -> Result<AccountAllocSize, ProgramError> {
+    let previous_account_size = account_info.try_data_len()?;
+    let new_account_size =
+        get_alloc_account_size::<V>(account_info, length, allow_repetition, layout)?;
+    Ok(AccountAllocSize {
+        new_account_size,
+        rent_delta: rent
+            .minimum_balance(new_account_size)
+            .saturating_sub(rent.minimum_balance(previous_account_size)),
+    })
+}
+
+/// Allocates a new TLV entry for the given `SplDiscriminate`, growing the
+/// account by exactly the size of the new entry's type, length, and value.
+/// Returns the repetition number of the new entry.
+///
+/// If the account ends with a checksum entry, the checksum covers the zeroed
+/// value, so call `update_checksum` on a state unpacked `with_checksum` after
+/// writing the value.
+pub fn alloc_with_account_realloc<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    length: usize,
+    allow_repetition: bool,
+) -> Result<usize, ProgramError> {
+    alloc_with_account_realloc_with_layout::<V>(
+        account_info,
+        length,
+        allow_repetition,
+        TlvLayout::DEFAULT,
+    )
+}
+
+/// Allocates a new TLV entry like `alloc_with_account_realloc`, using the
+/// given layout. A trailing checksum needs the same explicit update after
+/// writing the value.
+pub fn alloc_with_account_realloc_with_layout<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    length: usize,
+    allow_repetition: bool,
+    layout: TlvLayout,
+) -> Result<usize, ProgramError> {
+    let new_account_size =
+        get_alloc_account_size::<V>(account_info, length, allow_repetition, layout)?;
+    account_info.realloc(new_account_size, true)?;
+    let mut buffer = account_info.try_borrow_mut_data()?;
+    let mut state = unpack_account_state(&mut buffer, layout)?;
+    let (_, repetition_number) = state.alloc::<V>(length, allow_repetition)?;
+    Ok(repetition_number)
+}
+
+/// Allocates and initializes a new TLV entry with the default value, growing
+/// the account by exactly the size of the new entry. Returns the repetition
+/// number of the new entry.
+pub fn init_value_with_account_realloc<V: SplDiscriminate + Pod + Default>(
+    account_info: &impl TlvAccount,
+    allow_repetition: bool,
+) -> Result<usize, ProgramError> {
+    init_value_with_account_realloc_with_layout::<V>(
+        account_info,
+        allow_repetition,
+        TlvLayout::DEFAULT,
+    )
+}
+
+/// Allocates and initializes a new TLV entry like
+/// `init_value_with_account_realloc`, using the given layout
+pub fn init_value_with_account_realloc_with_layout<V: SplDiscriminate + Pod + Default>(
+    account_info: &impl TlvAccount,
+    allow_repetition: bool,
+    layout: TlvLayout,
+) -> Result<usize, ProgramError> {
+    let new_account_size =
+        get_alloc_account_size::<V>(account_info, size_of::<V>(), allow_repetition, layout)?;
+    account_info.realloc(new_account_size, true)?;
+    let mut buffer = account_info.try_borrow_mut_data()?;
+    let mut state = unpack_account_state(&mut buffer, layout)?;
+    let (_, repetition_number) = state.init_value::<V>(allow_repetition)?;
+    Ok(repetition_number)
+}
+
+/// Checks that a new entry can be added to the account, and returns the
+/// account size needed for it
+fn get_alloc_account_size<V: SplDiscriminate>(
+    account_info: &impl TlvAccount,
+    length: usize,
+    allow_repetition: bool,
+    layout: TlvLayout,
+) -> Result<usize, ProgramError> {
+    let data = account_info.try_borrow_data()?;
+    let state = TlvStateBorrowed::unpack_with_layout(&data, layout)?;
+    // check before reallocating, to avoid growing the account for nothing
+    if !allow_repetition && state.get_first_bytes::<V>().is_ok() {
+        return Err(TlvError::TypeAlreadyExists.into());
+    }
+    Ok(data.len().saturating_add(layout.entry_len(length)))
+}
+
+/// Unpacks the TLV data of an account for the realloc helpers, keeping the
+/// checksum up to date if the last entry is a `TlvChecksum`
+fn unpack_account_state(
+    data: &mut [u8],
+    layout: TlvLayout,
+) -> Result<TlvStateMut<'_>, ProgramError> {
+    let state = TlvStateMut::unpack_with_layout(data, layout)?;
+    let (checksum_range, _) = state.find_checksum()?;
+    Ok(if checksum_range.is_some() {
+        state.with_checksum()
+    } else {
+        state
+    })
+}
+
 /// Get the base size required for TLV data
 const fn get_base_len() -> usize {
-    get_indices_unchecked(0, 0).value_start
+    TlvLayout::DEFAULT.base_len()
 }
 
-fn check_data(tlv_data: &[u8]) -> Result<(), ProgramError> {
+fn check_data(layout: &TlvLayout, tlv_data: &[u8]) -> Result<(), ProgramError> {
     // should be able to iterate through all entries in the TLV structure
-    let _ = get_discriminators_and_end_index(tlv_data)?;
-    Ok(())
+    TlvIter::new(*layout, tlv_data).try_for_each(|entry| entry.map(|_| ()))
 }
 
 #[cfg(test)]
 mod test {
     use {
         super::*,
+        crate::account::InMemoryAccount,
+        crate::length::{Length, LengthSize},
         bytemuck::{Pod, Zeroable},
+        solana_account_info::AccountInfo,
+        solana_pubkey::Pubkey,
     };
 
     const TEST_BUFFER: &[u8] = &[
@@ -702,15 +2033,15 @@ mod test {
         let mut buffer = vec![0, 3];
         assert_eq!(
             TlvStateBorrowed::unpack(&buffer),
-            Err(ProgramError::InvalidAccountData)
+            Err(TlvError::TruncatedHeader.into())
         );
         assert_eq!(
             TlvStateMut::unpack(&mut buffer),
-            Err(ProgramError::InvalidAccountData)
+            Err(TlvError::TruncatedHeader.into())
         );
         assert_eq!(
-            TlvStateMut::unpack(&mut buffer),
-            Err(ProgramError::InvalidAccountData)
+            TlvStateOwned::unpack(buffer),
+            Err(TlvError::TruncatedHeader.into())
         );
 
         // tweak the discriminator
@@ -727,7 +2058,7 @@ mod test {
         buffer[ArrayDiscriminator::LENGTH] += 10;
         assert_eq!(
             TlvStateMut::unpack(&mut buffer),
-            Err(ProgramError::InvalidAccountData)
+            Err(TlvError::LengthOverflow.into())
         );
 
         // tweak the length, too small
@@ -743,7 +2074,81 @@ mod test {
         let buffer = &TEST_BUFFER[..TEST_BUFFER.len() - 5];
         assert_eq!(
             TlvStateBorrowed::unpack(buffer),
-            Err(ProgramError::InvalidAccountData)
+            Err(TlvError::LengthOverflow.into())
+        );
+    }
+
+    #[test]
+    fn validate_opaque_buffer() {
+        let state = TlvStateBorrowed::unpack(TEST_BUFFER).unwrap();
+        assert_eq!(state.validate(), Ok(()));
+
+        // partial header at the end
+        let buffer = [&TEST_BUFFER[..TEST_BUFFER.len() - 2], &[2; 10]].concat();
+        let state = TlvStateOwned {
+            data: buffer,
+            layout: TlvLayout::DEFAULT,
+        };
+        assert_eq!(
+            state.validate(),
+            Err(vec![TlvDataError::TruncatedHeader { offset: 44 }])
+        );
+
+        // length too big
+        let mut buffer = TEST_BUFFER.to_vec();
+        buffer[ArrayDiscriminator::LENGTH] += 10;
+        let state = TlvStateMut {
+            data: &mut buffer,
+            layout: TlvLayout::DEFAULT,
+            checksum: false,
+        };
+        assert_eq!(
+            state.validate(),
+            Err(vec![TlvDataError::LengthOverflow {
+                offset: 0,
+                length: 42
+            }])
+        );
+
+        // two independent corruptions, both reported
+        let mut buffer = vec![0; get_base_len() * 3 + 32 + 3 + 32 + 8];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        state.init_value::<TestValue>(true).unwrap().0.data = [0xaa; 32];
+        state.init_value::<TestSmallValue>(true).unwrap().0.data = [0xbb; 3];
+        state.init_value::<TestValue>(true).unwrap().0.data = [0xcc; 32];
+        let first_length_start = ArrayDiscriminator::LENGTH;
+        buffer[first_length_start + 3] = 1;
+        let last_start = get_base_len() * 2 + 32 + 3;
+        let last_length_start = last_start + ArrayDiscriminator::LENGTH;
+        buffer[last_length_start + 2] = 1;
+        let state = TlvStateBorrowed {
+            data: &buffer,
+            layout: TlvLayout::DEFAULT,
+        };
+        assert_eq!(
+            state.validate(),
+            Err(vec![
+                TlvDataError::LengthOverflow {
+                    offset: 0,
+                    length: 32 + (1 << 24)
+                },
+                TlvDataError::LengthOverflow {
+                    offset: last_start,
+                    length: 32 + (1 << 16)
+                },
+            ])
+        );
+
+        // garbage after the uninitialized entry, which unpacking allows
+        let mut buffer = TEST_BIG_BUFFER.to_vec();
+        let garbage_offset = buffer.len() - 3;
+        buffer[garbage_offset] = 1;
+        let state = TlvStateBorrowed::unpack(&buffer).unwrap();
+        assert_eq!(
+            state.validate(),
+            Err(vec![TlvDataError::TrailingData {
+                offset: garbage_offset
+            }])
         );
     }
 
@@ -751,21 +2156,109 @@ mod test {
     fn get_discriminators_with_opaque_buffer() {
         // incorrect due to the length
         assert_eq!(
-            get_discriminators_and_end_index(&[1, 0, 1, 1]).unwrap_err(),
-            ProgramError::InvalidAccountData,
+            get_discriminators_and_end_index(&TlvLayout::DEFAULT, &[1, 0, 1, 1]).unwrap_err(),
+            TlvError::TruncatedHeader.into(),
         );
         // correct due to the good discriminator length and zero length
         assert_eq!(
-            get_discriminators_and_end_index(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap(),
+            get_discriminators_and_end_index(
+                &TlvLayout::DEFAULT,
+                &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+            )
+            .unwrap(),
             (vec![ArrayDiscriminator::from(1)], 12)
         );
         // correct since it's just uninitialized data
         assert_eq!(
-            get_discriminators_and_end_index(&[0, 0, 0, 0, 0, 0, 0, 0]).unwrap(),
+            get_discriminators_and_end_index(&TlvLayout::DEFAULT, &[0, 0, 0, 0, 0, 0, 0, 0])
+                .unwrap(),
             (vec![], 0)
         );
     }
 
+    #[test]
+    fn iter_entries() {
+        let account_size = get_base_len() * 3
+            + size_of::<TestValue>() * 2
+            + size_of::<TestSmallValue>()
+            + ArrayDiscriminator::LENGTH;
+        let mut buffer = vec![0; account_size];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        state.init_value::<TestValue>(true).unwrap().0.data = [1; 32];
+        state.init_value::<TestSmallValue>(true).unwrap().0.data = [2; 3];
+        state.init_value::<TestValue>(true).unwrap().0.data = [3; 32];
+
+        let entries = state.iter().collect::<Result<Vec<_>, _>>().unwrap();
+        let first_value_start = get_base_len();
+        let second_value_start = first_value_start + size_of::<TestValue>() + get_base_len();
+        let third_value_start = second_value_start + size_of::<TestSmallValue>() + get_base_len();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    TestValue::SPL_DISCRIMINATOR,
+                    0,
+                    first_value_start,
+                    &[1; 32][..]
+                ),
+                (
+                    TestSmallValue::SPL_DISCRIMINATOR,
+                    0,
+                    second_value_start,
+                    &[2; 3][..]
+                ),
+                (
+                    TestValue::SPL_DISCRIMINATOR,
+                    1,
+                    third_value_start,
+                    &[3; 32][..]
+                ),
+            ]
+        );
+
+        // mutate every entry in place
+        for entry in state.iter_mut() {
+            let (_, repetition_number, _, value) = entry.unwrap();
+            value[0] = 100 + repetition_number as u8;
+        }
+        assert_eq!(state.get_first_value::<TestValue>().unwrap().data[0], 100);
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap().data,
+            [100, 2, 2]
+        );
+        assert_eq!(
+            state
+                .get_value_with_repetition::<TestValue>(1)
+                .unwrap()
+                .data[0],
+            101
+        );
+        let value_starts = state
+            .iter_mut()
+            .map(|entry| entry.unwrap().2)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            value_starts,
+            vec![first_value_start, second_value_start, third_value_start]
+        );
+    }
+
+    #[test]
+    fn iter_malformed_buffer() {
+        let mut buffer = TEST_BUFFER.to_vec();
+        buffer[ArrayDiscriminator::LENGTH] += 10;
+        let mut iter = TlvIter::new(TlvLayout::DEFAULT, &buffer);
+        assert_eq!(iter.next(), Some(Err(TlvError::LengthOverflow.into())));
+        assert_eq!(iter.next(), None);
+        let mut iter = TlvIterMut::new(TlvLayout::DEFAULT, &mut buffer);
+        assert_eq!(iter.next(), Some(Err(TlvError::LengthOverflow.into())));
+        assert_eq!(iter.next(), None);
+
+        // trailing uninitialized bytes are skipped
+        let state = TlvStateBorrowed::unpack(TEST_BUFFER).unwrap();
+        assert_eq!(state.iter().count(), 1);
+    }
+
     #[test]
     fn value_pack_unpack() {
         let account_size =
@@ -940,10 +2433,10 @@ mod test {
         discriminator_ref.copy_from_slice(TestValue::SPL_DISCRIMINATOR.as_ref());
         state.data[ArrayDiscriminator::LENGTH] = 32;
         let err = state.get_first_value::<TestValue>().unwrap_err();
-        assert_eq!(err, ProgramError::InvalidAccountData);
+        assert_eq!(err, TlvError::LengthOverflow.into());
         assert_eq!(
             state.get_discriminators().unwrap_err(),
-            ProgramError::InvalidAccountData
+            TlvError::LengthOverflow.into()
         );
     }
 
@@ -1040,7 +2533,8 @@ mod test {
         assert_eq!(data, [0; SMALL_SIZE]);
         let value = state.get_first_value::<TestNonZeroDefault>().unwrap();
         assert_eq!(*value, TestNonZeroDefault::default());
-        let (_, end_index) = get_discriminators_and_end_index(&buffer).unwrap();
+        let (_, end_index) =
+            get_discriminators_and_end_index(&TlvLayout::DEFAULT, &buffer).unwrap();
         assert_eq!(
             &buffer[end_index..ACCOUNT_SIZE],
             [0; TLV_SIZE + EXTRA_SPACE - SMALL_SIZE]
@@ -1100,7 +2594,8 @@ mod test {
         assert_eq!(*value, [0; TLV_SIZE]);
         let value = state.get_first_value::<TestNonZeroDefault>().unwrap();
         assert_eq!(*value, TestNonZeroDefault::default());
-        let (_, end_index) = get_discriminators_and_end_index(&buffer).unwrap();
+        let (_, end_index) =
+            get_discriminators_and_end_index(&TlvLayout::DEFAULT, &buffer).unwrap();
         assert_eq!(
             &buffer[end_index..ACCOUNT_SIZE],
             [0; TLV_SIZE + EXTRA_SPACE - SMALL_SIZE]
@@ -1117,33 +2612,1181 @@ mod test {
         );
     }
 
-    #[derive(Clone, Debug, PartialEq)]
-    struct TestVariableLen {
-        data: String, // test with a variable length type
-    }
-    impl SplDiscriminate for TestVariableLen {
-        const SPL_DISCRIMINATOR: ArrayDiscriminator =
-            ArrayDiscriminator::new([5; ArrayDiscriminator::LENGTH]);
-    }
-    impl VariableLenPack for TestVariableLen {
-        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
-            let bytes = self.data.as_bytes();
-            let end = 8 + bytes.len();
-            if dst.len() < end {
-                Err(ProgramError::InvalidAccountData)
-            } else {
-                dst[..8].copy_from_slice(&self.data.len().to_le_bytes());
-                dst[8..end].copy_from_slice(bytes);
-                Ok(())
-            }
-        }
-        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-            let length = u64::from_le_bytes(src[..8].try_into().unwrap()) as usize;
-            if src[8..8 + length].len() != length {
-                return Err(ProgramError::InvalidAccountData);
-            }
-            let data = std::str::from_utf8(&src[8..8 + length])
-                .unwrap()
+    #[test]
+    fn disjoint_mutable_entries() {
+        let account_size = get_base_len() * 4
+            + size_of::<TestValue>() * 2
+            + size_of::<TestSmallValue>()
+            + size_of::<TestNonZeroDefault>();
+        let mut buffer = vec![0; account_size];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestValue>(true).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        let _ = state.init_value::<TestValue>(true).unwrap();
+        let _ = state.init_value::<TestNonZeroDefault>(false).unwrap();
+
+        let (value, small_value, non_zero) = state
+            .get_many_mut::<(TestValue, TestSmallValue, TestNonZeroDefault)>()
+            .unwrap();
+        value.data = [1; 32];
+        small_value.data = non_zero.data[..3].try_into().unwrap();
+        non_zero.data = [5; 5];
+        assert_eq!(state.get_first_value::<TestValue>().unwrap().data, [1; 32]);
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap().data,
+            TEST_NON_ZERO_DEFAULT_DATA[..3]
+        );
+
+        // same type with different repetitions
+        let (first, second) = state
+            .get_many_mut_with_repetition::<(TestValue, TestValue)>(&[1, 0])
+            .unwrap();
+        first.data = [2; 32];
+        std::mem::swap(first, second);
+        assert_eq!(state.get_first_value::<TestValue>().unwrap().data, [2; 32]);
+        assert_eq!(
+            state
+                .get_value_with_repetition::<TestValue>(1)
+                .unwrap()
+                .data,
+            [1; 32]
+        );
+
+        // same entry twice
+        assert_eq!(
+            state
+                .get_many_mut::<(TestValue, TestSmallValue, TestValue)>()
+                .unwrap_err(),
+            TlvError::DuplicateEntry.into()
+        );
+        // missing entry
+        assert_eq!(
+            state
+                .get_many_mut_with_repetition::<(TestValue, TestSmallValue)>(&[2, 0])
+                .unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+        // wrong number of repetitions
+        assert_eq!(
+            state
+                .get_many_mut_with_repetition::<(TestValue, TestSmallValue)>(&[0])
+                .unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+
+        // raw bytes, in request order
+        let values = state
+            .get_many_bytes_mut(&[
+                (TestNonZeroDefault::SPL_DISCRIMINATOR, 0),
+                (TestValue::SPL_DISCRIMINATOR, 1),
+            ])
+            .unwrap();
+        assert_eq!(values[0], [5; 5]);
+        assert_eq!(values[1], [1; 32]);
+    }
+
+    #[test]
+    fn nested_containers() {
+        let mut buffer = vec![0; 256];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        let _ = state.alloc::<TestEmptyValue>(0, false).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+
+        // allocate inside the empty outer entry, which grows it
+        let (value, repetition_number) = state
+            .alloc_nested::<TestEmptyValue, TestSmallValue>(0, 3, true)
+            .unwrap();
+        value.copy_from_slice(&[7; 3]);
+        assert_eq!(repetition_number, 0);
+        let (_, repetition_number) = state
+            .alloc_nested::<TestEmptyValue, TestSmallValue>(0, 3, true)
+            .unwrap();
+        assert_eq!(repetition_number, 1);
+        assert_eq!(
+            state
+                .alloc_nested::<TestEmptyValue, TestSmallValue>(0, 3, false)
+                .unwrap_err(),
+            TlvError::TypeAlreadyExists.into()
+        );
+        assert_eq!(
+            state.get_first_bytes::<TestEmptyValue>().unwrap().len(),
+            (get_base_len() + 3) * 2
+        );
+
+        // path lookups
+        let nested = state.get_nested::<TestEmptyValue>().unwrap();
+        assert_eq!(
+            nested.get_first_value::<TestSmallValue>().unwrap().data,
+            [7; 3]
+        );
+        assert_eq!(nested.count::<TestSmallValue>().unwrap(), 2);
+        state
+            .get_nested_mut::<TestEmptyValue>()
+            .unwrap()
+            .get_value_with_repetition_mut::<TestSmallValue>(1)
+            .unwrap()
+            .data = [8; 3];
+
+        // grow the first nested entry, the outer length follows
+        let value = state
+            .realloc_nested_first::<TestEmptyValue, TestSmallValue>(10)
+            .unwrap();
+        assert_eq!(value, [7, 7, 7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            state.get_first_bytes::<TestEmptyValue>().unwrap().len(),
+            get_base_len() * 2 + 13
+        );
+        let nested = state.get_nested::<TestEmptyValue>().unwrap();
+        assert_eq!(
+            nested
+                .get_value_with_repetition::<TestSmallValue>(1)
+                .unwrap()
+                .data,
+            [8; 3]
+        );
+        // the entries around the outer one are untouched
+        assert_eq!(
+            state.get_first_value::<TestValue>().unwrap(),
+            &TestValue::default()
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+
+        // shrink it again
+        let value = state
+            .realloc_nested_with_repetition::<TestEmptyValue, TestSmallValue>(0, 0, 1)
+            .unwrap();
+        assert_eq!(value, [7]);
+        assert_eq!(
+            state.get_first_bytes::<TestEmptyValue>().unwrap().len(),
+            get_base_len() * 2 + 4
+        );
+
+        // remove a nested entry, the outer one shrinks
+        let removed = state
+            .remove_nested::<TestEmptyValue, TestSmallValue>(0, 0)
+            .unwrap();
+        assert_eq!(removed, get_base_len() + 1);
+        let nested = state.get_nested::<TestEmptyValue>().unwrap();
+        assert_eq!(
+            nested.get_first_value::<TestSmallValue>().unwrap().data,
+            [8; 3]
+        );
+        assert_eq!(
+            state.get_first_bytes::<TestEmptyValue>().unwrap().len(),
+            get_base_len() + 3
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+    }
+
+    #[test]
+    fn alloc_with_account_realloc_size() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut buffer = vec![0; get_base_len() + size_of::<TestValue>()];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut buffer,
+            &owner,
+            false,
+            0,
+        );
+        let rent = Rent::default();
+
+        let size =
+            get_alloc_with_account_realloc_size::<TestSmallValue>(&account_info, 3, false, &rent)
+                .unwrap();
+        let new_account_size = get_base_len() * 2 + size_of::<TestValue>() + 3;
+        assert_eq!(
+            size,
+            AccountAllocSize {
+                new_account_size,
+                rent_delta: rent.minimum_balance(new_account_size)
+                    - rent.minimum_balance(get_base_len() + size_of::<TestValue>()),
+            }
+        );
+
+        assert_eq!(
+            get_alloc_with_account_realloc_size::<TestValue>(&account_info, 32, false, &rent)
+                .unwrap_err(),
+            TlvError::TypeAlreadyExists.into()
+        );
+        let size = get_alloc_with_account_realloc_size::<TestValue>(&account_info, 32, true, &rent)
+            .unwrap();
+        assert_eq!(
+            size.new_account_size,
+            (get_base_len() + size_of::<TestValue>()) * 2
+        );
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestHeader {
+        flags: u8,
+        data: [u8; 3],
+    }
+
+    struct TestHeaderTail {
+        _header: TestHeader,
+        _items: [[u8; 2]],
+    }
+    impl SplDiscriminate for TestHeaderTail {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([7; ArrayDiscriminator::LENGTH]);
+    }
+    impl PodHeaderTail for TestHeaderTail {
+        type Header = TestHeader;
+        type Item = [u8; 2];
+    }
+
+    #[test]
+    fn header_tail_values() {
+        let header = TestHeader {
+            flags: 1,
+            data: [2; 3],
+        };
+        let mut buffer = vec![0; 64];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state
+            .alloc_and_pack_header_tail_entry::<TestHeaderTail>(&header, &[[3; 2]], false)
+            .unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+
+        let (read_header, items) = state.get_first_header_tail::<TestHeaderTail>().unwrap();
+        assert_eq!(read_header, &header);
+        assert_eq!(items, [[3; 2]]);
+
+        state
+            .push_first_header_tail_item::<TestHeaderTail>(&[4; 2])
+            .unwrap();
+        let (read_header, items) = state.get_first_header_tail_mut::<TestHeaderTail>().unwrap();
+        read_header.flags = 5;
+        items[0] = [6; 2];
+        let (read_header, items) = state.get_first_header_tail::<TestHeaderTail>().unwrap();
+        assert_eq!(read_header.flags, 5);
+        assert_eq!(items, [[6; 2], [4; 2]]);
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+
+        // a value too short for the header can't be read or pushed to
+        let mut buffer = vec![0; 64];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.alloc::<TestHeaderTail>(2, false).unwrap();
+        assert_eq!(
+            state.get_first_header_tail::<TestHeaderTail>().unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(
+            state
+                .push_first_header_tail_item::<TestHeaderTail>(&[4; 2])
+                .unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValueV2 {
+        data: [u8; 32],
+        extra: [u8; 4],
+    }
+    impl SplDiscriminate for TestValueV2 {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([8; ArrayDiscriminator::LENGTH]);
+    }
+    impl TlvMigrate for TestValueV2 {
+        type Previous = TestValue;
+
+        fn get_migrated_len(_previous: &[u8]) -> Result<usize, ProgramError> {
+            Ok(size_of::<Self>())
+        }
+
+        fn migrate(previous: &[u8], dst: &mut [u8]) -> Result<(), ProgramError> {
+            let previous = pod_from_bytes::<TestValue>(previous)?;
+            *pod_from_bytes_mut::<Self>(dst)? = Self {
+                data: previous.data,
+                extra: [9; 4],
+            };
+            Ok(())
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValueV3 {
+        data: [u8; 2],
+    }
+    impl SplDiscriminate for TestValueV3 {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([9; ArrayDiscriminator::LENGTH]);
+    }
+    impl TlvMigrate for TestValueV3 {
+        type Previous = TestValueV2;
+
+        fn get_migrated_len(_previous: &[u8]) -> Result<usize, ProgramError> {
+            Ok(size_of::<Self>())
+        }
+
+        fn migrate(previous: &[u8], dst: &mut [u8]) -> Result<(), ProgramError> {
+            let previous = pod_from_bytes::<TestValueV2>(previous)?;
+            dst.copy_from_slice(&previous.extra[..2]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn migrate_entries() {
+        let mut buffer = vec![0; 256];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let value = state.init_value::<TestValue>(true).unwrap().0;
+        value.data = [1; 32];
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        let value = state.init_value::<TestValue>(true).unwrap().0;
+        value.data = [2; 32];
+
+        // grow
+        assert_eq!(state.migrate_all::<TestValueV2>().unwrap(), 2);
+        assert_eq!(state.count::<TestValue>().unwrap(), 0);
+        assert_eq!(
+            state.get_discriminators().unwrap(),
+            [
+                TestValueV2::SPL_DISCRIMINATOR,
+                TestSmallValue::SPL_DISCRIMINATOR,
+                TestValueV2::SPL_DISCRIMINATOR,
+            ]
+        );
+        assert_eq!(
+            state.get_all_values::<TestValueV2>().unwrap(),
+            [
+                &TestValueV2 {
+                    data: [1; 32],
+                    extra: [9; 4],
+                },
+                &TestValueV2 {
+                    data: [2; 32],
+                    extra: [9; 4],
+                },
+            ]
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+
+        // nothing left to migrate
+        assert_eq!(state.migrate_all::<TestValueV2>().unwrap(), 0);
+
+        // shrink
+        assert_eq!(state.migrate_all::<TestValueV3>().unwrap(), 2);
+        assert_eq!(
+            state.get_all_values::<TestValueV3>().unwrap(),
+            [&TestValueV3 { data: [9; 2] }, &TestValueV3 { data: [9; 2] }]
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+        let (_, end_index) =
+            get_discriminators_and_end_index(&TlvLayout::DEFAULT, state.get_data()).unwrap();
+        assert_eq!(end_index, get_base_len() * 3 + 2 + 3 + 2);
+        assert!(state.get_data()[end_index..].iter().all(|&x| x == 0));
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestVersionedV1 {
+        version: u8,
+        data: [u8; 3],
+    }
+    impl SplDiscriminate for TestVersionedV1 {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([10; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestVersionedV2 {
+        version: u8,
+        data: [u8; 3],
+        extra: [u8; 2],
+    }
+    impl SplDiscriminate for TestVersionedV2 {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator = TestVersionedV1::SPL_DISCRIMINATOR;
+    }
+    impl TlvMigrate for TestVersionedV2 {
+        type Previous = TestVersionedV1;
+        const VERSION: Option<u8> = Some(2);
+
+        fn get_migrated_len(_previous: &[u8]) -> Result<usize, ProgramError> {
+            Ok(size_of::<Self>())
+        }
+
+        fn migrate(previous: &[u8], dst: &mut [u8]) -> Result<(), ProgramError> {
+            let previous = pod_from_bytes::<TestVersionedV1>(previous)?;
+            *pod_from_bytes_mut::<Self>(dst)? = Self {
+                version: 0,
+                data: previous.data,
+                extra: [7; 2],
+            };
+            Ok(())
+        }
+    }
+
+    struct TestUnversioned;
+    impl SplDiscriminate for TestUnversioned {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator = TestVersionedV1::SPL_DISCRIMINATOR;
+    }
+    impl TlvMigrate for TestUnversioned {
+        type Previous = TestVersionedV1;
+
+        fn get_migrated_len(previous: &[u8]) -> Result<usize, ProgramError> {
+            Ok(previous.len())
+        }
+
+        fn migrate(previous: &[u8], dst: &mut [u8]) -> Result<(), ProgramError> {
+            dst.copy_from_slice(previous);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn migrate_versioned_entries() {
+        let mut buffer = vec![0; 128];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        *state.init_value::<TestVersionedV1>(true).unwrap().0 = TestVersionedV1 {
+            version: 1,
+            data: [1; 3],
+        };
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        *state.init_value::<TestVersionedV1>(true).unwrap().0 = TestVersionedV1 {
+            version: 1,
+            data: [2; 3],
+        };
+
+        // both versions share a discriminator, so it needs a version byte
+        assert_eq!(
+            state.migrate_all::<TestUnversioned>().unwrap_err(),
+            TlvError::MissingVersion.into()
+        );
+
+        assert_eq!(state.migrate_all::<TestVersionedV2>().unwrap(), 2);
+        assert_eq!(
+            state.get_all_values::<TestVersionedV2>().unwrap(),
+            [
+                &TestVersionedV2 {
+                    version: 2,
+                    data: [1; 3],
+                    extra: [7; 2],
+                },
+                &TestVersionedV2 {
+                    version: 2,
+                    data: [2; 3],
+                    extra: [7; 2],
+                },
+            ]
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap(),
+            &TestSmallValue::default()
+        );
+
+        // entries with the current version byte are left alone
+        assert_eq!(state.migrate_all::<TestVersionedV2>().unwrap(), 0);
+        *state.init_value::<TestVersionedV1>(true).unwrap().0 = TestVersionedV1 {
+            version: 1,
+            data: [3; 3],
+        };
+        assert_eq!(state.migrate_all::<TestVersionedV2>().unwrap(), 1);
+        assert_eq!(
+            state
+                .get_value_with_repetition::<TestVersionedV2>(2)
+                .unwrap(),
+            &TestVersionedV2 {
+                version: 2,
+                data: [3; 3],
+                extra: [7; 2],
+            }
+        );
+        assert_eq!(
+            state.get_first_value::<TestVersionedV2>().unwrap().data,
+            [1; 3]
+        );
+    }
+
+    #[test]
+    fn canonicalize_entries() {
+        let mut buffer = vec![0; 256];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestNonZeroDefault>(false).unwrap();
+        state.init_value::<TestSmallValue>(true).unwrap().0.data = [1; 3];
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        state.init_value::<TestSmallValue>(true).unwrap().0.data = [2; 3];
+        let _ = state.alloc::<TestEmptyValue>(0, false).unwrap();
+
+        let mut other_buffer = vec![0; 256];
+        let mut other_state = TlvStateMut::unpack(&mut other_buffer).unwrap();
+        let _ = other_state.alloc::<TestEmptyValue>(0, false).unwrap();
+        other_state
+            .init_value::<TestSmallValue>(true)
+            .unwrap()
+            .0
+            .data = [1; 3];
+        other_state
+            .init_value::<TestSmallValue>(true)
+            .unwrap()
+            .0
+            .data = [2; 3];
+        let _ = other_state.init_value::<TestValue>(false).unwrap();
+        let _ = other_state.init_value::<TestNonZeroDefault>(false).unwrap();
+
+        state.canonicalize().unwrap();
+        other_state.canonicalize().unwrap();
+        assert_eq!(
+            state.get_discriminators().unwrap(),
+            [
+                TestValue::SPL_DISCRIMINATOR,
+                TestSmallValue::SPL_DISCRIMINATOR,
+                TestSmallValue::SPL_DISCRIMINATOR,
+                TestEmptyValue::SPL_DISCRIMINATOR,
+                TestNonZeroDefault::SPL_DISCRIMINATOR,
+            ]
+        );
+        assert_eq!(
+            state.get_all_values::<TestSmallValue>().unwrap(),
+            [
+                &TestSmallValue { data: [1; 3] },
+                &TestSmallValue { data: [2; 3] }
+            ]
+        );
+        assert_eq!(state.get_data(), other_state.get_data());
+    }
+
+    #[test]
+    fn defragment_buffer() {
+        let layout = TlvLayout::new(2, LengthSize::U8).with_value_alignment(4);
+        let mut buffer = vec![0; 32];
+        let mut state = TlvStateMut::unpack_with_layout(&mut buffer, layout).unwrap();
+        state
+            .alloc::<TestCompactValue>(3, false)
+            .unwrap()
+            .0
+            .copy_from_slice(&[1; 3]);
+        state
+            .alloc::<TestOtherCompactValue>(1, false)
+            .unwrap()
+            .0
+            .copy_from_slice(&[2]);
+        // leave stale bytes in the padding and free space
+        let data = state.get_data_mut();
+        data[7] = 9;
+        data[13] = 9;
+        data[20] = 9;
+        assert!(state.validate().is_err());
+
+        assert_eq!(state.defragment().unwrap(), 32 - 16);
+        assert_eq!(state.validate(), Ok(()));
+        assert_eq!(
+            state.get_data(),
+            &[
+                1, 0, 3, 0, // header
+                1, 1, 1, 0, // value and padding
+                2, 1, 1, 0, // header
+                2, 0, 0, 0, // value and padding
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // free space
+            ]
+        );
+    }
+
+    #[test]
+    fn checksum_entry() {
+        let checksum_len = get_base_len() + size_of::<TlvChecksum>();
+        let mut buffer = vec![0; 256];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap().with_checksum();
+        let _ = state.init_value::<TestValue>(true).unwrap();
+        state
+            .alloc_and_pack_variable_len_entry(
+                &TestVariableLen {
+                    data: "hello".to_string(),
+                },
+                false,
+            )
+            .unwrap();
+        let _ = state.init_value::<TestValue>(true).unwrap();
+        let end = get_base_len() * 3 + size_of::<TestValue>() * 2 + 13;
+        let check = |state: &TlvStateMut, end: usize| {
+            assert_eq!(
+                state.get_first_value::<TlvChecksum>().unwrap(),
+                &TlvChecksum::compute(&state.get_data()[..end])
+            );
+            assert_eq!(
+                state.get_discriminators().unwrap().last(),
+                Some(&TlvChecksum::SPL_DISCRIMINATOR)
+            );
+            assert_eq!(state.count::<TlvChecksum>().unwrap(), 1);
+            TlvStateBorrowed::unpack_verified(state.get_data()).unwrap();
+        };
+        check(&state, end);
+
+        // pack, realloc and remove keep it up to date
+        state
+            .pack_first_variable_len_value(&TestVariableLen {
+                data: "world".to_string(),
+            })
+            .unwrap();
+        check(&state, end);
+        let _ = state.realloc_first::<TestVariableLen>(15).unwrap();
+        check(&state, end + 2);
+        state.remove::<TestValue>(1).unwrap();
+        check(&state, end + 2 - get_base_len() - size_of::<TestValue>());
+        state.canonicalize().unwrap();
+        check(&state, end + 2 - get_base_len() - size_of::<TestValue>());
+
+        // writing through references needs an explicit update
+        state.get_first_value_mut::<TestValue>().unwrap().data = [1; 32];
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(state.get_data()).unwrap_err(),
+            TlvError::ChecksumMismatch.into()
+        );
+        state.update_checksum().unwrap();
+        let end = end + 2 - get_base_len() - size_of::<TestValue>();
+        check(&state, end);
+
+        // so does writing to allocated or reallocated slices
+        state.alloc::<TestEmptyValue>(1, false).unwrap().0[0] = 1;
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(state.get_data()).unwrap_err(),
+            TlvError::ChecksumMismatch.into()
+        );
+        state.update_checksum().unwrap();
+        check(&state, end + get_base_len() + 1);
+        state.realloc_first::<TestEmptyValue>(2).unwrap()[1] = 1;
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(state.get_data()).unwrap_err(),
+            TlvError::ChecksumMismatch.into()
+        );
+        state.update_checksum().unwrap();
+        check(&state, end + get_base_len() + 2);
+        state.remove_first::<TestEmptyValue>().unwrap();
+        check(&state, end);
+
+        // corruption
+        let mut corrupted = buffer.clone();
+        corrupted[end - 1] ^= 1;
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(&corrupted).unwrap_err(),
+            TlvError::ChecksumMismatch.into()
+        );
+        let mut corrupted = buffer.clone();
+        corrupted[end + checksum_len + 1] = 1;
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(&corrupted).unwrap_err(),
+            TlvError::TrailingData.into()
+        );
+
+        // no room left for the checksum, nothing changes
+        let mut buffer = vec![0; get_base_len() + size_of::<TestValue>() + checksum_len];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap().with_checksum();
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        let expected = state.get_data().to_vec();
+        assert_eq!(
+            state.init_value::<TestEmptyValue>(false).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(
+            state.realloc_first::<TestValue>(33).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(state.get_data(), expected);
+        TlvStateBorrowed::unpack_verified(&buffer).unwrap();
+
+        // an earlier checksum entry is left alone, only the last one is updated
+        let mut buffer = vec![0; 128];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TlvChecksum>(false).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        let mut state = state.with_checksum();
+        state.update_checksum().unwrap();
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        state.remove_first::<TestSmallValue>().unwrap();
+        assert_eq!(
+            state.get_discriminators().unwrap(),
+            [
+                TlvChecksum::SPL_DISCRIMINATOR,
+                TestValue::SPL_DISCRIMINATOR,
+                TlvChecksum::SPL_DISCRIMINATOR
+            ]
+        );
+        assert_eq!(
+            state.get_first_value::<TlvChecksum>().unwrap(),
+            &TlvChecksum::default()
+        );
+        TlvStateBorrowed::unpack_verified(&buffer).unwrap();
+
+        // no checksum at all
+        let mut buffer = vec![0; 64];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestValue>(false).unwrap();
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(&buffer).unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+    }
+
+    #[test]
+    fn remove_entries() {
+        const TLV_SIZE: usize = 10;
+        const ACCOUNT_SIZE: usize = get_base_len()
+            + size_of::<TestNonZeroDefault>()
+            + get_base_len()
+            + TLV_SIZE
+            + get_base_len()
+            + TLV_SIZE
+            + get_base_len()
+            + size_of::<TestSmallValue>();
+        let mut buffer = vec![0; ACCOUNT_SIZE];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+
+        let _ = state.init_value::<TestNonZeroDefault>(false).unwrap();
+        state.alloc::<TestValue>(TLV_SIZE, true).unwrap().0.fill(1);
+        state.alloc::<TestValue>(TLV_SIZE, true).unwrap().0.fill(2);
+        state.init_value::<TestSmallValue>(false).unwrap().0.data = [3; 3];
+
+        // remove the first repetition, the second one takes its place
+        let removed_bytes = state.remove::<TestValue>(0).unwrap();
+        assert_eq!(removed_bytes, get_base_len() + TLV_SIZE);
+        assert_eq!(state.get_first_bytes::<TestValue>().unwrap(), [2; TLV_SIZE]);
+        assert_eq!(
+            state.get_bytes_with_repetition::<TestValue>(1).unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+        assert_eq!(
+            *state.get_first_value::<TestNonZeroDefault>().unwrap(),
+            TestNonZeroDefault::default()
+        );
+        assert_eq!(
+            state.get_first_value::<TestSmallValue>().unwrap().data,
+            [3; 3]
+        );
+        assert_eq!(
+            &state.get_discriminators().unwrap(),
+            &[
+                TestNonZeroDefault::SPL_DISCRIMINATOR,
+                TestValue::SPL_DISCRIMINATOR,
+                TestSmallValue::SPL_DISCRIMINATOR,
+            ]
+        );
+
+        // remove the last entry by its discriminator
+        let removed_bytes = state
+            .remove_by_discriminator(TestSmallValue::SPL_DISCRIMINATOR, 0)
+            .unwrap();
+        assert_eq!(removed_bytes, get_base_len() + size_of::<TestSmallValue>());
+
+        // removing a missing entry fails
+        assert_eq!(
+            state.remove_first::<TestSmallValue>().unwrap_err(),
+            TlvError::TypeNotFound.into()
+        );
+
+        // the freed space is zeroed out and can be used again
+        let (_, end_index) = get_discriminators_and_end_index(&state.layout, state.data).unwrap();
+        assert_eq!(
+            end_index,
+            ACCOUNT_SIZE - removed_bytes - get_base_len() - TLV_SIZE
+        );
+        assert!(state.data[end_index..].iter().all(|&x| x == 0));
+        state.alloc::<TestValue>(TLV_SIZE, true).unwrap().0.fill(4);
+        state.init_value::<TestSmallValue>(false).unwrap();
+        assert_eq!(
+            state.get_bytes_with_repetition::<TestValue>(1).unwrap(),
+            [4; TLV_SIZE]
+        );
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestCompactValue {
+        data: [u8; 4],
+    }
+    impl SplDiscriminate for TestCompactValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestOtherCompactValue {
+        data: u8,
+    }
+    impl SplDiscriminate for TestOtherCompactValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn compact_layout() {
+        let layout = TlvLayout::new(2, LengthSize::U16);
+        let buffer = [
+            1, 0, // first type's discriminator
+            4, 0, // first type's length
+            1, 2, 3, 4, // first type's value
+            2, 1, // second type's discriminator
+            1, 0, // second type's length
+            9, // second type's value
+            0, 0, 0, 0, // uninitialized
+        ];
+        let state = TlvStateBorrowed::unpack_with_layout(&buffer, layout).unwrap();
+        assert_eq!(
+            state.get_first_value::<TestCompactValue>().unwrap().data,
+            [1, 2, 3, 4]
+        );
+        assert_eq!(
+            state
+                .get_first_value::<TestOtherCompactValue>()
+                .unwrap()
+                .data,
+            9
+        );
+        assert_eq!(
+            state.get_discriminators().unwrap(),
+            vec![
+                TestCompactValue::SPL_DISCRIMINATOR,
+                TestOtherCompactValue::SPL_DISCRIMINATOR
+            ]
+        );
+        // the default layout cannot read it
+        assert_eq!(
+            TlvStateBorrowed::unpack(&buffer).unwrap_err(),
+            TlvError::LengthOverflow.into()
+        );
+
+        // write the same buffer with the compact layout
+        let mut other_buffer = vec![0; buffer.len()];
+        let mut state = TlvStateMut::unpack_with_layout(&mut other_buffer, layout).unwrap();
+        state.init_value::<TestCompactValue>(false).unwrap().0.data = [1, 2, 3, 4];
+        state
+            .init_value::<TestOtherCompactValue>(false)
+            .unwrap()
+            .0
+            .data = 9;
+        assert_eq!(
+            state.init_value::<TestSmallValue>(false).unwrap_err(),
+            TlvError::DiscriminatorTooLarge.into()
+        );
+        assert_eq!(other_buffer, buffer);
+
+        // realloc and remove with the compact layout
+        let mut state = TlvStateMut::unpack_with_layout(&mut other_buffer, layout).unwrap();
+        state.realloc_first::<TestCompactValue>(2).unwrap();
+        assert_eq!(
+            state
+                .get_first_value::<TestOtherCompactValue>()
+                .unwrap()
+                .data,
+            9
+        );
+        assert_eq!(state.remove_first::<TestCompactValue>().unwrap(), 6);
+        assert_eq!(
+            other_buffer,
+            [2, 1, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        // lengths that do not fit in a single byte are rejected
+        let mut buffer = vec![0; 300];
+        let mut state =
+            TlvStateMut::unpack_with_layout(&mut buffer, TlvLayout::new(1, LengthSize::U8))
+                .unwrap();
+        assert_eq!(
+            state.alloc::<TestCompactValue>(256, false).unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct TestCompactVariableLen {
+        data: Vec<u8>,
+    }
+    impl SplDiscriminate for TestCompactVariableLen {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([3, 0, 0, 0, 0, 0, 0, 0]);
+    }
+    impl VariableLenPack for TestCompactVariableLen {
+        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+            dst[..self.data.len()].copy_from_slice(&self.data);
+            Ok(())
+        }
+        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+            Ok(Self { data: src.to_vec() })
+        }
+        fn get_packed_len(&self) -> Result<usize, ProgramError> {
+            Ok(self.data.len())
+        }
+    }
+
+    #[test]
+    fn compact_layout_account_realloc() {
+        let layout = TlvLayout::new(2, LengthSize::U16);
+        let account = InMemoryAccount::new(vec![]);
+        let rent = Rent::default();
+
+        let size = get_alloc_with_account_realloc_size_with_layout::<TestCompactValue>(
+            &account, 4, false, &rent, layout,
+        )
+        .unwrap();
+        assert_eq!(size.new_account_size, 8);
+        assert_eq!(
+            init_value_with_account_realloc_with_layout::<TestCompactValue>(
+                &account, false, layout
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(
+            alloc_with_account_realloc_with_layout::<TestOtherCompactValue>(
+                &account, 1, false, layout
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(
+            init_value_with_account_realloc_with_layout::<TestCompactValue>(
+                &account, false, layout
+            )
+            .unwrap_err(),
+            TlvError::TypeAlreadyExists.into()
+        );
+        assert_eq!(account.try_data_len().unwrap(), 13);
+
+        // grow and shrink a variable-length entry
+        let variable_len = TestCompactVariableLen { data: vec![7; 3] };
+        assert_eq!(
+            alloc_with_account_realloc_with_layout::<TestCompactVariableLen>(
+                &account, 1, false, layout
+            )
+            .unwrap(),
+            0
+        );
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 20);
+        let variable_len = TestCompactVariableLen { data: vec![8] };
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 18);
+
+        // remove the first entry
+        assert_eq!(
+            realloc_and_remove_with_repetition_with_layout::<TestCompactValue>(&account, 0, layout)
+                .unwrap(),
+            8
+        );
+        assert_eq!(account.into_data(), [2, 1, 1, 0, 0, 3, 0, 1, 0, 8]);
+    }
+
+    #[test]
+    fn aligned_layout_account_realloc() {
+        let layout = TlvLayout::DEFAULT.with_value_alignment(8);
+        let account = InMemoryAccount::new(vec![]);
+        let variable_len = TestCompactVariableLen { data: vec![7; 8] };
+        alloc_with_account_realloc_with_layout::<TestCompactVariableLen>(
+            &account, 8, false, layout,
+        )
+        .unwrap();
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        init_value_with_account_realloc_with_layout::<TestAlignedValue>(&account, false, layout)
+            .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 56);
+
+        // growing past the alignment adds a whole padded block
+        let variable_len = TestCompactVariableLen { data: vec![8; 9] };
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 64);
+        {
+            let data = account.try_borrow_data().unwrap();
+            let state = TlvStateBorrowed::unpack_with_layout(&data, layout).unwrap();
+            assert_eq!(
+                state
+                    .get_first_variable_len_value::<TestCompactVariableLen>()
+                    .unwrap(),
+                variable_len
+            );
+            assert_eq!(
+                state.get_first_bytes::<TestAlignedValue>().unwrap(),
+                [0; 16]
+            );
+        }
+
+        // shrinking keeps the padding of the entry
+        let variable_len = TestCompactVariableLen { data: vec![9; 7] };
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 56);
+        let variable_len = TestCompactVariableLen { data: vec![6; 6] };
+        realloc_and_pack_variable_len_with_repetition_with_layout(
+            &account,
+            &variable_len,
+            0,
+            layout,
+        )
+        .unwrap();
+        assert_eq!(account.try_data_len().unwrap(), 56);
+        let data = account.try_borrow_data().unwrap();
+        let state = TlvStateBorrowed::unpack_with_layout(&data, layout).unwrap();
+        assert_eq!(
+            state
+                .get_first_variable_len_value::<TestCompactVariableLen>()
+                .unwrap(),
+            variable_len
+        );
+        assert_eq!(
+            state.get_first_bytes::<TestAlignedValue>().unwrap(),
+            [0; 16]
+        );
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestAlignedValue {
+        amount: u64,
+        data: [u8; 8],
+    }
+    impl SplDiscriminate for TestAlignedValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([6; ArrayDiscriminator::LENGTH]);
+    }
+
+    #[test]
+    fn aligned_values() {
+        let layout = TlvLayout::DEFAULT.with_value_alignment(8);
+        // back the buffer with `u64`s so that it's aligned in memory
+        let mut words = [0u64; 16];
+        let buffer = bytemuck::cast_slice_mut::<u64, u8>(&mut words);
+
+        // unpadded layout puts the value at a misaligned offset
+        let mut state = TlvStateMut::unpack(buffer).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        assert_eq!(
+            state.init_value::<TestAlignedValue>(false).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        buffer.fill(0);
+
+        let mut state = TlvStateMut::unpack_with_layout(buffer, layout).unwrap();
+        state.init_value::<TestSmallValue>(false).unwrap().0.data = [1; 3];
+        state
+            .init_value::<TestAlignedValue>(false)
+            .unwrap()
+            .0
+            .amount = u64::MAX;
+        assert_eq!(
+            state.iter().map(|e| e.unwrap().2).collect::<Vec<_>>(),
+            vec![16, 40]
+        );
+        assert_eq!(
+            state.get_first_value::<TestAlignedValue>().unwrap().amount,
+            u64::MAX
+        );
+
+        // grow within the padding, then past it, still aligned
+        assert_eq!(
+            state.realloc_first::<TestSmallValue>(5).unwrap(),
+            [1, 1, 1, 0, 0]
+        );
+        assert_eq!(
+            state.get_first_value::<TestAlignedValue>().unwrap().amount,
+            u64::MAX
+        );
+        state.realloc_first::<TestSmallValue>(10).unwrap();
+        assert_eq!(
+            state.get_first_value::<TestAlignedValue>().unwrap().amount,
+            u64::MAX
+        );
+
+        // shrink again, the old value bytes become zeroed padding
+        state.realloc_first::<TestSmallValue>(3).unwrap();
+        assert_eq!(state.get_data()[16..24], [1, 1, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            state.get_first_value::<TestAlignedValue>().unwrap().amount,
+            u64::MAX
+        );
+
+        // remove the first entry with its padding
+        assert_eq!(state.remove_first::<TestSmallValue>().unwrap(), 24);
+        assert_eq!(
+            state.get_first_value::<TestAlignedValue>().unwrap().amount,
+            u64::MAX
+        );
+        assert_eq!(state.validate(), Ok(()));
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestVariableLen {
+        data: String, // test with a variable length type
+    }
+    impl SplDiscriminate for TestVariableLen {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([5; ArrayDiscriminator::LENGTH]);
+    }
+    impl VariableLenPack for TestVariableLen {
+        fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+            let bytes = self.data.as_bytes();
+            let end = 8 + bytes.len();
+            if dst.len() < end {
+                Err(ProgramError::InvalidAccountData)
+            } else {
+                dst[..8].copy_from_slice(&self.data.len().to_le_bytes());
+                dst[8..end].copy_from_slice(bytes);
+                Ok(())
+            }
+        }
+        fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+            let length = u64::from_le_bytes(src[..8].try_into().unwrap()) as usize;
+            if src[8..8 + length].len() != length {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let data = std::str::from_utf8(&src[8..8 + length])
+                .unwrap()
                 .to_string();
             Ok(Self { data })
         }
@@ -1152,6 +3795,158 @@ mod test {
         }
     }
 
+    #[test]
+    fn realloc_in_memory_account() {
+        let mut buffer = vec![0; get_base_len() + size_of::<TestSmallValue>()];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        let account = InMemoryAccount::new(buffer);
+
+        // add entries, growing by exactly their size
+        let variable_len = TestVariableLen {
+            data: "hello".to_string(),
+        };
+        let packed_len = variable_len.get_packed_len().unwrap();
+        assert_eq!(
+            alloc_with_account_realloc::<TestVariableLen>(&account, packed_len, false).unwrap(),
+            0
+        );
+        assert_eq!(
+            init_value_with_account_realloc::<TestNonZeroDefault>(&account, true).unwrap(),
+            0
+        );
+        assert_eq!(
+            init_value_with_account_realloc::<TestNonZeroDefault>(&account, true).unwrap(),
+            1
+        );
+        assert_eq!(
+            init_value_with_account_realloc::<TestSmallValue>(&account, false).unwrap_err(),
+            TlvError::TypeAlreadyExists.into()
+        );
+        let base_size =
+            get_base_len() * 4 + size_of::<TestSmallValue>() + size_of::<TestNonZeroDefault>() * 2;
+        assert_eq!(account.try_data_len().unwrap(), base_size + packed_len);
+
+        // grow and shrink a variable-length entry
+        let variable_len = TestVariableLen {
+            data: "hello world".to_string(),
+        };
+        realloc_and_pack_first_variable_len(&account, &variable_len).unwrap();
+        assert_eq!(
+            account.try_data_len().unwrap(),
+            base_size + variable_len.get_packed_len().unwrap()
+        );
+        let variable_len = TestVariableLen {
+            data: "hi".to_string(),
+        };
+        realloc_and_pack_first_variable_len(&account, &variable_len).unwrap();
+        assert_eq!(
+            account.try_data_len().unwrap(),
+            base_size + variable_len.get_packed_len().unwrap()
+        );
+        {
+            let data = account.try_borrow_data().unwrap();
+            let state = TlvStateBorrowed::unpack(&data).unwrap();
+            assert_eq!(
+                state
+                    .get_first_variable_len_value::<TestVariableLen>()
+                    .unwrap(),
+                variable_len
+            );
+            assert_eq!(
+                state.get_all_values::<TestNonZeroDefault>().unwrap(),
+                [
+                    &TestNonZeroDefault::default(),
+                    &TestNonZeroDefault::default()
+                ]
+            );
+        }
+
+        // remove everything but the first entry
+        realloc_and_remove_first::<TestVariableLen>(&account).unwrap();
+        realloc_and_remove_with_repetition::<TestNonZeroDefault>(&account, 1).unwrap();
+        realloc_and_remove_first::<TestNonZeroDefault>(&account).unwrap();
+        let mut expected = vec![0; get_base_len() + size_of::<TestSmallValue>()];
+        let mut state = TlvStateMut::unpack(&mut expected).unwrap();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        assert_eq!(account.into_data(), expected);
+    }
+
+    #[test]
+    fn realloc_checksummed_account() {
+        let checksum_len = get_base_len() + size_of::<TlvChecksum>();
+        let mut buffer = vec![0; get_base_len() + size_of::<TestSmallValue>() + checksum_len];
+        let mut state = TlvStateMut::unpack(&mut buffer).unwrap().with_checksum();
+        let _ = state.init_value::<TestSmallValue>(false).unwrap();
+        let account = InMemoryAccount::new(buffer);
+        let check = |entries_len: usize| {
+            let data = account.try_borrow_data().unwrap();
+            assert_eq!(data.len(), entries_len + checksum_len);
+            let state = TlvStateBorrowed::unpack_verified(&data).unwrap();
+            assert_eq!(
+                state.get_discriminators().unwrap().last(),
+                Some(&TlvChecksum::SPL_DISCRIMINATOR)
+            );
+        };
+        let base_size = get_base_len() + size_of::<TestSmallValue>();
+        check(base_size);
+
+        // adding entries grows the account and moves the checksum
+        let variable_len = TestVariableLen {
+            data: "hello".to_string(),
+        };
+        let packed_len = variable_len.get_packed_len().unwrap();
+        assert_eq!(
+            alloc_with_account_realloc::<TestVariableLen>(&account, packed_len, false).unwrap(),
+            0
+        );
+        let base_size = base_size + get_base_len() + packed_len;
+        check(base_size);
+        // writing the allocated value needs an explicit update
+        {
+            let mut data = account.try_borrow_mut_data().unwrap();
+            let mut state = TlvStateMut::unpack(&mut data).unwrap();
+            state.pack_first_variable_len_value(&variable_len).unwrap();
+            assert_eq!(
+                TlvStateBorrowed::unpack_verified(state.get_data()).unwrap_err(),
+                TlvError::ChecksumMismatch.into()
+            );
+            state.with_checksum().update_checksum().unwrap();
+        }
+        check(base_size);
+        assert_eq!(
+            init_value_with_account_realloc::<TestNonZeroDefault>(&account, false).unwrap(),
+            0
+        );
+        let base_size = base_size + get_base_len() + size_of::<TestNonZeroDefault>();
+        check(base_size);
+
+        // grow and shrink a variable-length entry
+        let variable_len = TestVariableLen {
+            data: "hello world".to_string(),
+        };
+        realloc_and_pack_first_variable_len(&account, &variable_len).unwrap();
+        check(base_size + 6);
+        let variable_len = TestVariableLen {
+            data: "hi".to_string(),
+        };
+        realloc_and_pack_first_variable_len(&account, &variable_len).unwrap();
+        check(base_size - 3);
+        assert_eq!(
+            TlvStateBorrowed::unpack_verified(&account.try_borrow_data().unwrap())
+                .unwrap()
+                .get_first_variable_len_value::<TestVariableLen>()
+                .unwrap(),
+            variable_len
+        );
+
+        // remove entries, shrinking the account
+        realloc_and_remove_first::<TestVariableLen>(&account).unwrap();
+        check(base_size - get_base_len() - packed_len);
+        realloc_and_remove_first::<TestNonZeroDefault>(&account).unwrap();
+        check(get_base_len() + size_of::<TestSmallValue>());
+    }
+
     #[test]
     fn first_variable_len_value() {
         let initial_data = "This is a pretty cool test!";
@@ -1268,6 +4063,59 @@ mod test {
         );
     }
 
+    #[test]
+    fn get_all_repetitions() {
+        let values = ["first", "second entry", "the third one"].map(|data| TestVariableLen {
+            data: data.to_string(),
+        });
+        let mut buffer = vec![];
+        let mut small_values = vec![];
+        for (i, value) in values.iter().enumerate() {
+            buffer.extend(vec![
+                0;
+                get_base_len() * 2
+                    + value.get_packed_len().unwrap()
+                    + size_of::<TestSmallValue>()
+            ]);
+            let mut state = TlvStateMut::unpack(&mut buffer).unwrap();
+            state
+                .alloc_and_pack_variable_len_entry(value, true)
+                .unwrap();
+            let small_value = state.init_value::<TestSmallValue>(true).unwrap().0;
+            small_value.data = [i as u8; 3];
+            small_values.push(*small_value);
+        }
+        let state = TlvStateBorrowed::unpack(&buffer).unwrap();
+
+        assert_eq!(state.count::<TestVariableLen>().unwrap(), 3);
+        assert_eq!(state.count::<TestSmallValue>().unwrap(), 3);
+        assert_eq!(state.count::<TestValue>().unwrap(), 0);
+        assert_eq!(
+            state
+                .get_all_variable_len_values::<TestVariableLen>()
+                .unwrap(),
+            values
+        );
+        assert_eq!(
+            state.get_all_values::<TestSmallValue>().unwrap(),
+            small_values.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            state.get_all_bytes::<TestSmallValue>().unwrap(),
+            vec![[0; 3], [1; 3], [2; 3]]
+        );
+        assert!(state.get_all_values::<TestValue>().unwrap().is_empty());
+
+        // wrong size for the type
+        let mut buffer = TEST_BIG_BUFFER.to_vec();
+        buffer[ArrayDiscriminator::LENGTH] -= 1;
+        let state = TlvStateBorrowed::unpack(&buffer).unwrap();
+        assert_eq!(
+            state.get_all_values::<TestValue>().unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+    }
+
     #[test]
     fn add_entry_mix_and_match() {
         let mut buffer = vec![];
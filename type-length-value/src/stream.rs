@@ -0,0 +1,377 @@
+//! Streaming TLV reader and writer, for data too large to hold in memory
+
+use {
+    crate::{
+        error::{TlvDataError, TlvStreamError},
+        layout::TlvLayout,
+        variable_len_pack::VariableLenPack,
+    },
+    bytemuck::Pod,
+    spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
+    spl_pod::bytemuck::pod_bytes_of,
+    std::io::{self, Read, Write},
+};
+
+/// Reads as many bytes as possible into `buf`, stopping early only at the end
+/// of the stream, and returns the number of bytes read
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Streaming reader over TLV entries, yielding the discriminator and value of
+/// each one without holding the whole buffer in memory.
+///
+/// Iteration stops at the end of the stream or at the first uninitialized
+/// discriminator. Errors contain offsets from the start of the stream, and
+/// end the iteration.
+#[derive(Debug)]
+pub struct TlvReader<R: Read> {
+    reader: R,
+    layout: TlvLayout,
+    offset: usize,
+    done: bool,
+}
+
+impl<R: Read> TlvReader<R> {
+    /// Creates a reader over TLV entries with the default layout
+    pub fn new(reader: R) -> Self {
+        Self::new_with_layout(reader, TlvLayout::DEFAULT)
+    }
+
+    /// Creates a reader over TLV entries with the given layout
+    pub fn new_with_layout(reader: R, layout: TlvLayout) -> Self {
+        Self {
+            reader,
+            layout,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Gets the number of bytes read from the stream so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the underlying reader, positioned after the last byte read
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next entry, or `None` if there are no more entries
+    fn read_entry(&mut self) -> Result<Option<(ArrayDiscriminator, Vec<u8>)>, TlvStreamError> {
+        let type_start = self.offset;
+        let truncated_header = TlvDataError::TruncatedHeader { offset: type_start };
+
+        let mut header = vec![0; self.layout.base_len()];
+        let discriminator_size = self.layout.discriminator_size();
+        let read = read_fully(&mut self.reader, &mut header[..discriminator_size])?;
+        self.offset += read;
+        if read < discriminator_size {
+            if header[..read].iter().all(|&x| x == 0) {
+                // end of the stream, possibly after a few uninitialized bytes
+                return Ok(None);
+            }
+            return Err(truncated_header.into());
+        }
+        let discriminator = self
+            .layout
+            .read_discriminator(&header[..discriminator_size])?;
+        if discriminator == ArrayDiscriminator::UNINITIALIZED {
+            return Ok(None);
+        }
+
+        let read = read_fully(&mut self.reader, &mut header[discriminator_size..])?;
+        self.offset += read;
+        if discriminator_size + read < header.len() {
+            return Err(truncated_header.into());
+        }
+        let length_end = discriminator_size + self.layout.length_size().size();
+        let length = self
+            .layout
+            .length_size()
+            .read(&header[discriminator_size..length_end])?;
+
+        // don't trust the length for allocating, only read what's there
+        let padded_length = self.layout.padded_len(length);
+        let mut value = vec![];
+        let read = (&mut self.reader)
+            .take(padded_length as u64)
+            .read_to_end(&mut value)?;
+        self.offset += read;
+        if read < padded_length {
+            return Err(TlvDataError::LengthOverflow {
+                offset: type_start,
+                length,
+            }
+            .into());
+        }
+        value.truncate(length);
+        Ok(Some((discriminator, value)))
+    }
+}
+
+impl<R: Read> Iterator for TlvReader<R> {
+    type Item = Result<(ArrayDiscriminator, Vec<u8>), TlvStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Streaming writer of TLV entries, producing the same bytes as allocating the
+/// entries one after the other in a `TlvStateMut`
+#[derive(Debug)]
+pub struct TlvWriter<W: Write> {
+    writer: W,
+    layout: TlvLayout,
+    offset: usize,
+}
+
+impl<W: Write> TlvWriter<W> {
+    /// Creates a writer of TLV entries with the default layout
+    pub fn new(writer: W) -> Self {
+        Self::new_with_layout(writer, TlvLayout::DEFAULT)
+    }
+
+    /// Creates a writer of TLV entries with the given layout
+    pub fn new_with_layout(writer: W, layout: TlvLayout) -> Self {
+        Self {
+            writer,
+            layout,
+            offset: 0,
+        }
+    }
+
+    /// Gets the number of bytes written to the stream so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes an entry with the given discriminator and value bytes, returning
+    /// the offset of the value in the stream
+    pub fn write_entry(
+        &mut self,
+        discriminator: ArrayDiscriminator,
+        value: &[u8],
+    ) -> Result<usize, TlvStreamError> {
+        let mut header = vec![0; self.layout.base_len()];
+        let discriminator_size = self.layout.discriminator_size();
+        self.layout
+            .write_discriminator(discriminator, &mut header[..discriminator_size])?;
+        let length_size = self.layout.length_size();
+        length_size.write(
+            value.len(),
+            &mut header[discriminator_size..discriminator_size + length_size.size()],
+        )?;
+        let padding = vec![0; self.layout.padded_len(value.len()) - value.len()];
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(value)?;
+        self.writer.write_all(&padding)?;
+        let value_start = self.offset + header.len();
+        self.offset = value_start + value.len() + padding.len();
+        Ok(value_start)
+    }
+
+    /// Writes an entry for the given Pod value, returning the offset of the
+    /// value in the stream
+    pub fn write_value<V: SplDiscriminate + Pod>(
+        &mut self,
+        value: &V,
+    ) -> Result<usize, TlvStreamError> {
+        self.write_entry(V::SPL_DISCRIMINATOR, pod_bytes_of(value))
+    }
+
+    /// Writes an entry for the given variable-length value, returning the
+    /// offset of the value in the stream
+    pub fn write_variable_len_value<V: SplDiscriminate + VariableLenPack>(
+        &mut self,
+        value: &V,
+    ) -> Result<usize, TlvStreamError> {
+        let mut data = vec![0; value.get_packed_len()?];
+        value.pack_into_slice(&mut data)?;
+        self.write_entry(V::SPL_DISCRIMINATOR, &data)
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> Result<(), TlvStreamError> {
+        self.writer.flush().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            length::LengthSize,
+            state::{TlvState, TlvStateMut},
+        },
+        bytemuck::Zeroable,
+        std::io::Cursor,
+    };
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+    struct TestValue {
+        data: [u8; 3],
+    }
+    impl SplDiscriminate for TestValue {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    struct TestBytes;
+    impl SplDiscriminate for TestBytes {
+        const SPL_DISCRIMINATOR: ArrayDiscriminator =
+            ArrayDiscriminator::new([2, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn write_and_read_entries() {
+        for layout in [
+            TlvLayout::DEFAULT,
+            TlvLayout::new(2, LengthSize::U16).with_value_alignment(8),
+        ] {
+            let mut writer = TlvWriter::new_with_layout(vec![], layout);
+            writer.write_value(&TestValue { data: [1; 3] }).unwrap();
+            writer
+                .write_entry(TestBytes::SPL_DISCRIMINATOR, &[2; 10])
+                .unwrap();
+            writer.write_value(&TestValue { data: [3; 3] }).unwrap();
+            let offset = writer.offset();
+            let written = writer.into_inner();
+            assert_eq!(written.len(), offset);
+
+            // same bytes as building it in memory
+            let mut buffer = vec![0; offset];
+            let mut state = TlvStateMut::unpack_with_layout(&mut buffer, layout).unwrap();
+            state.init_value::<TestValue>(true).unwrap().0.data = [1; 3];
+            state.alloc::<TestBytes>(10, false).unwrap().0.fill(2);
+            state.init_value::<TestValue>(true).unwrap().0.data = [3; 3];
+            assert_eq!(state.get_data(), written);
+
+            // uninitialized bytes at the end are ignored
+            let mut stream = written.clone();
+            stream.extend_from_slice(&[0; 20]);
+            let entries = TlvReader::new_with_layout(Cursor::new(stream), layout)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(
+                entries,
+                [
+                    (TestValue::SPL_DISCRIMINATOR, vec![1; 3]),
+                    (TestBytes::SPL_DISCRIMINATOR, vec![2; 10]),
+                    (TestValue::SPL_DISCRIMINATOR, vec![3; 3]),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn stop_at_uninitialized() {
+        let mut writer = TlvWriter::new(vec![]);
+        writer.write_value(&TestValue { data: [1; 3] }).unwrap();
+        let end = writer.offset();
+        let mut stream = writer.into_inner();
+        // another blob after the sentinel
+        stream.extend_from_slice(&[0; 8]);
+        stream.extend_from_slice(&[9; 20]);
+
+        let mut reader = TlvReader::new(Cursor::new(stream));
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            (TestValue::SPL_DISCRIMINATOR, vec![1; 3])
+        );
+        assert!(reader.next().is_none());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), end + 8);
+        assert_eq!(reader.into_inner().position() as usize, end + 8);
+    }
+
+    #[test]
+    fn read_truncated_entries() {
+        let mut writer = TlvWriter::new(vec![]);
+        writer.write_value(&TestValue { data: [1; 3] }).unwrap();
+        let second_entry = writer.offset();
+        writer
+            .write_entry(TestBytes::SPL_DISCRIMINATOR, &[2; 10])
+            .unwrap();
+        let stream = writer.into_inner();
+
+        // value cut short
+        let mut reader = TlvReader::new(Cursor::new(&stream[..stream.len() - 1]));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err(),
+            TlvStreamError::Data(TlvDataError::LengthOverflow { offset, length: 10 })
+                if offset == second_entry
+        ));
+        assert!(reader.next().is_none());
+
+        // header cut short
+        let mut reader = TlvReader::new(Cursor::new(&stream[..second_entry + 10]));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err(),
+            TlvStreamError::Data(TlvDataError::TruncatedHeader { offset })
+                if offset == second_entry
+        ));
+
+        // discriminator cut short
+        let mut reader = TlvReader::new(Cursor::new(&stream[..second_entry + 1]));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err(),
+            TlvStreamError::Data(TlvDataError::TruncatedHeader { offset })
+                if offset == second_entry
+        ));
+    }
+
+    #[test]
+    fn write_too_large_discriminator() {
+        let mut writer = TlvWriter::new_with_layout(vec![], TlvLayout::new(1, LengthSize::U8));
+        assert!(matches!(
+            writer
+                .write_entry(TestBytes::SPL_DISCRIMINATOR, &[])
+                .unwrap_err(),
+            TlvStreamError::Program(_)
+        ));
+        assert!(matches!(
+            writer
+                .write_entry(TestValue::SPL_DISCRIMINATOR, &[0; 256])
+                .unwrap_err(),
+            TlvStreamError::Program(_)
+        ));
+        assert_eq!(writer.offset(), 0);
+        assert!(writer.into_inner().is_empty());
+    }
+}